@@ -0,0 +1,262 @@
+//! Decode throughput benchmark.
+//!
+//! Run with `cargo bench --bench decode`. Each case compresses a set of strings once, then decompresses them
+//! repeatedly and reports the throughput in compressed bytes and strings per second.
+//!
+//! Each case is also decompressed by the decoder from before the cursor rewrite (see the `reference` module), which read
+//! every byte of a code through bincode, so the two are printed side by side. The old decoder only knows the codes that
+//! existed then, so it is given the strings that only use those, and the `old` and `new` lines say how many of them there are.
+//! It looks entries up in plain vectors of strings rather than the crate's tables, so it isn't an exact copy of the old
+//! implementation, and the `new` line is the fairer one to compare it with.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use byte_size::builder::Builder;
+use byte_size::engine::Engine;
+
+const TEXT: &str = "The quick brown fox jumped over the lazy dog. It was the best of times, it was the worst of times, \
+it was the age of wisdom, it was the age of foolishness. Please contact support@example.com or visit \
+https://www.example.com/help?id=12345 before 2023-04-01. Order #998877 shipped to 42 Wallaby Way, Sydney.";
+
+const UNICODE: &str = "Grüße aus München! Ça va très bien, merci. Привет, мир. こんにちは世界 🙂🚀 naïve café résumé";
+
+const TELEMETRY: &str = "temp=21.5 hum=40 bat=3.71 rssi=-97 node=7 status=ok";
+
+//Runs `decode` until at least `duration` has passed, returning the number of iterations and the time taken
+fn measure<F: FnMut()>(duration: Duration, mut decode: F) -> (u32, Duration) {
+    let start = Instant::now();
+    let mut iterations = 0;
+
+    while start.elapsed() < duration {
+        decode();
+        iterations += 1;
+    }
+
+    (iterations, start.elapsed())
+}
+
+//Decompresses every record with `decode` for a while, and prints the throughput
+fn report<F: FnMut(& [u8])>(name: & str, compressed: & [Vec<u8>], mut decode: F) {
+    let bytes: usize = compressed.iter().map(Vec::len).sum();
+
+    //Warm up
+    measure(Duration::from_millis(200), || {
+        for record in compressed {
+            decode(black_box(record));
+        }
+    });
+
+    let (iterations, elapsed) = measure(Duration::from_secs(2), || {
+        for record in compressed {
+            decode(black_box(record));
+        }
+    });
+
+    let seconds = elapsed.as_secs_f64();
+    let megabytes = (bytes as f64 * iterations as f64) / 1_000_000.0;
+    let records = compressed.len() as f64 * iterations as f64;
+
+    println!("{:<16} {:>8.2} MB/s {:>12.0} strings/s", name, megabytes / seconds, records / seconds);
+}
+
+fn bench(name: & str, engine: & Engine, reference: & reference::Tables, strings: & [String]) {
+    let compressed: Vec<Vec<u8>> = strings.iter().map(|s| engine.compress(s)).collect();
+
+    report(name, &compressed, |record| {
+        black_box(engine.decompress(record).unwrap());
+    });
+
+    //Only the records the old decoder can read, checking it reads them correctly
+    let old: Vec<Vec<u8>> = compressed.iter().zip(strings).filter(|(record, string)| reference::decompress(reference, record).as_ref() == Some(*string)).map(|(record, _)| record.clone()).collect();
+
+    if old.is_empty() {
+        println!("{:<16} no strings only use the old codes", format!("{} old", name));
+        return;
+    }
+
+    report(&format!("{} old {}/{}", name, old.len(), compressed.len()), &old, |record| {
+        black_box(reference::decompress(reference, record).unwrap());
+    });
+
+    report(&format!("{} new {}/{}", name, old.len(), compressed.len()), &old, |record| {
+        black_box(engine.decompress(record).unwrap());
+    });
+}
+
+//Decompresses from a reader rather than a slice, which goes through Engine::decompress_from
+fn bench_reader(name: & str, engine: & Engine, string: & str) {
+    let compressed = engine.compress(string);
+
+    let (iterations, elapsed) = measure(Duration::from_secs(2), || {
+        let mut decompressed = String::new();
+        engine.decompress_from(black_box(compressed.as_slice()), & mut decompressed).unwrap();
+        black_box(decompressed);
+    });
+
+    let megabytes = (compressed.len() as f64 * iterations as f64) / 1_000_000.0;
+
+    println!("{:<16} {:>8.2} MB/s {:>12.0} strings/s", name, megabytes / elapsed.as_secs_f64(), iterations as f64 / elapsed.as_secs_f64());
+}
+
+//Splits the text into short strings, similar to what byte-size is typically used for
+fn short_strings(text: & str) -> Vec<String> {
+    text.split(['.', ',', '!', '?'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+//The decoder from before the cursor rewrite, which read each byte of a code through bincode.
+//It only knows the codes that existed then (one byte wonders, unicode characters, two and three byte words, custom words,
+//repetitions, numbers and control characters), and returns None for anything else
+mod reference {
+
+    pub struct Tables {
+        one_byte: Vec<String>,
+        two_byte: Vec<String>,
+        three_byte: Vec<String>,
+        controls: Vec<String>,
+        repetitions: Vec<String>,
+    }
+
+    impl Tables {
+        //The lists the default engine is built from
+        pub fn builtin() -> Self {
+            Self {
+                one_byte: parse_list(include_str!("../lists/obw.txt")),
+                two_byte: parse_list(include_str!("../lists/tbc.txt")),
+                three_byte: parse_list(include_str!("../lists/tbu.txt")),
+                controls: parse_list(include_str!("../lists/controls.txt")),
+                repetitions: parse_list(include_str!("../lists/repetitions.txt")),
+            }
+        }
+    }
+
+    fn parse_list(list: & str) -> Vec<String> {
+        list.lines().map(percent_decode).collect()
+    }
+
+    fn percent_decode(line: & str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = line.as_bytes();
+
+        while let Some((first, tail)) = rest.split_first() {
+            //A % that isn't followed by two hex digits is left as it is, as in the build script
+            let escaped = tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+            if let (b'%', Some(byte)) = (*first, escaped) {
+                bytes.push(byte);
+                rest = &tail[2..];
+            } else {
+                bytes.push(*first);
+                rest = tail;
+            }
+        }
+
+        String::from_utf8(bytes).unwrap()
+    }
+
+    const ONE_BYTE_WONDER_COUNT: usize = 240;
+    const CUSTOM_COUNT: usize = 32;
+    const REPETITION_COUNT: usize = 32;
+    const NUMBER_COUNT: usize = 32;
+    const TWO_BYTE_COUNT: usize = 1793;
+    const THREE_BYTE_COUNT: usize = 16512;
+
+    fn read_byte(bytes: & mut & [u8]) -> Option<u8> {
+        bincode::deserialize_from(bytes).ok()
+    }
+
+    //Bincode reads a char as its utf-8 bytes
+    fn read_char(bytes: & mut & [u8]) -> Option<char> {
+        bincode::deserialize_from(bytes).ok()
+    }
+
+    fn push_word(string: & mut String, space: bool, word: & str) {
+        if space {
+            string.push(' ');
+        }
+
+        string.push_str(word);
+    }
+
+    pub fn decompress(tables: & Tables, mut bytes: & [u8]) -> Option<String> {
+        let mut string = String::new();
+
+        while !bytes.is_empty() {
+            let first = read_byte(& mut bytes)?;
+
+            if (first as usize) < ONE_BYTE_WONDER_COUNT {
+                string.push_str(&tables.one_byte[first as usize]);
+                continue;
+            }
+
+            if first as usize == ONE_BYTE_WONDER_COUNT {
+                let ch = read_char(& mut bytes)?;
+                string.push(ch);
+                continue;
+            }
+
+            let second = read_byte(& mut bytes)?;
+            let two_code = (first as usize - ONE_BYTE_WONDER_COUNT - 1) * 256 + second as usize;
+
+            let custom = TWO_BYTE_COUNT * 2;
+            let repetitions = custom + CUSTOM_COUNT;
+            let numbers = repetitions + REPETITION_COUNT;
+            let controls = numbers + NUMBER_COUNT;
+            let three_byte = controls + tables.controls.len();
+
+            if two_code < custom {
+                push_word(& mut string, two_code >= TWO_BYTE_COUNT, tables.two_byte.get(two_code % TWO_BYTE_COUNT)?);
+            } else if two_code < repetitions {
+                //The benchmark engines have no custom words
+                return None;
+            } else if two_code < numbers {
+                let count = two_code - repetitions;
+
+                //Counts below 4 are codes added since
+                if count < 4 {
+                    return None;
+                }
+
+                let index = read_byte(& mut bytes)?;
+                string.push_str(&tables.repetitions.get(index as usize)?.repeat(count));
+            } else if two_code < controls {
+                let comb = two_code - numbers;
+                let mut number = (comb / 8) as u128;
+
+                for i in 0..comb % 8 + 1 {
+                    let byte = read_byte(& mut bytes)?;
+                    number += (byte as u128) << (i * 8 + 2);
+                }
+
+                string.push_str(&number.to_string());
+            } else if two_code < three_byte {
+                string.push_str(&tables.controls[two_code - controls]);
+            } else {
+                let third = read_byte(& mut bytes)?;
+                let three_code = (two_code - three_byte) * 256 + third as usize;
+
+                if three_code >= THREE_BYTE_COUNT * 2 {
+                    return None;
+                }
+
+                push_word(& mut string, three_code >= THREE_BYTE_COUNT, tables.three_byte.get(three_code % THREE_BYTE_COUNT)?);
+            }
+        }
+
+        Some(string)
+    }
+}
+
+fn main() {
+    let engine = Builder::default().engine();
+    let reference = reference::Tables::builtin();
+
+    bench("english", &engine, &reference, &short_strings(TEXT));
+    bench("unicode", &engine, &reference, &short_strings(UNICODE));
+    bench("telemetry", &engine, &reference, &[TELEMETRY.to_string()]);
+    bench("long", &engine, &reference, &[TEXT.repeat(20)]);
+    bench_reader("reader", &engine, &(TEXT.to_string() + UNICODE).repeat(20));
+}
@@ -1,27 +1,125 @@
 
-use std::collections::{HashSet};
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, read_to_string};
 use std::io::{Write};
 use std::env;
 use std::path::Path;
 
-fn hash_generate_list<P: AsRef<Path>>(path: P, name: &str, code: & mut String, all_lengths: & mut HashSet<usize>) -> HashSet<usize> {
-    use std::fmt::Write;
+//The code space, which must match the layout in src/serde.rs and the limits in src/tables.rs
+const ONE_BYTE_LENGTH: usize = 240;
+const TWO_BYTE_MAX: usize = 1793;
+const THREE_BYTE_MAX: usize = 16512;
+//Values of the 15 two byte code leads (241 to 255), and how many of them are taken by custom strings, repetitions and numbers
+const TWO_BYTE_CODES: usize = 15 * 256;
+const CUSTOM_REPETITION_NUMBER_CODES: usize = 32 + 32 + 32;
+//Repetitions store the index of the repeated sequence in a single byte
+const REPETITION_MAX: usize = 256;
+
+//The order the lists are hashed in must match src/tables.rs
+include!("src/fnv.rs");
+include!("src/overlaps.rs");
+
+//A list file, percent-decoded and checked for unreadable, empty and duplicate entries. Errors name the file and line (starting at 1)
+struct List {
+    path: String,
+    entries: Vec<String>,
+    lines: HashMap<String, usize>,
+}
+
+impl List {
+
+    fn read(path: String) -> Result<Self, String> {
+        let s = read_to_string(&path).map_err(|e| format!("{}: could not be read: {}", path, e))?;
+
+        let mut entries = Vec::new();
+        let mut lines = HashMap::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let lemma = percent_encoding::percent_decode_str(line).decode_utf8().map_err(|_| format!("{}:{}: {:?} is not valid utf-8 once percent-decoded", path, i + 1, line))?;
+
+            if lemma.is_empty() {
+                return Err(format!("{}:{}: empty entry", path, i + 1));
+            }
+
+            if let Some(first) = lines.get(lemma.as_ref()) {
+                return Err(format!("{}:{}: {:?} is a duplicate of line {}", path, i + 1, lemma, first));
+            }
+
+            lines.insert(lemma.to_string(), i + 1);
+            entries.push(lemma.into_owned());
+        }
+
+        Ok(Self { path, entries, lines })
+    }
+
+    fn check_length(&self, min: usize, max: usize) -> Result<(), String> {
+        if self.entries.len() < min || self.entries.len() > max {
+            let expected = if min == max { format!("exactly {}", max) } else { format!("between {} and {}", min, max) };
+
+            return Err(format!("{}: has {} entries, but the code space needs {}", self.path, self.entries.len(), expected));
+        }
+
+        Ok(())
+    }
+
+    //Fails if any entry of this list is also in the other list, other than the allowed entries (each with its line in this list)
+    fn check_disjoint(&self, other: &List, allowed: &[(&str, usize)], reason: &str) -> Result<(), String> {
+        for (i, lemma) in self.entries.iter().enumerate() {
+            if let Some(line) = other.lines.get(lemma).filter(|_| !allowed.contains(&(lemma.as_str(), i + 1))) {
+                return Err(format!("{}:{}: {:?} is also in {}:{}, {}", self.path, i + 1, lemma, other.path, line, reason));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//Checks the lists fit in the code space, and that no entry can never be used (other than the known overlaps)
+fn validate(obw: &List, tbc: &List, tbu: &List, controls: &List, repetitions: &List) -> Result<(), String> {
+    obw.check_length(ONE_BYTE_LENGTH, ONE_BYTE_LENGTH)?;
+    tbc.check_length(0, TWO_BYTE_MAX)?;
+    tbu.check_length(0, THREE_BYTE_MAX)?;
+    repetitions.check_length(1, REPETITION_MAX)?;
+
+    //Controls take the two byte codes left between the numbers and the three byte codes, which need 2 * THREE_BYTE_MAX values spread over whole leads
+    let three_byte_leads = (2 * THREE_BYTE_MAX).div_ceil(256);
+    controls.check_length(0, TWO_BYTE_CODES - 2 * TWO_BYTE_MAX - CUSTOM_REPETITION_NUMBER_CODES - three_byte_leads)?;
+
+    for (i, control) in controls.entries.iter().enumerate() {
+        if control.len() != 1 {
+            return Err(format!("{}:{}: {:?} must be a single ascii character", controls.path, i + 1, control));
+        }
+    }
+
+    //Characters that don't match any other code are stored as the one byte wonder at their own position, so these must be left alone
+    for (index, lemma) in obw.entries.iter().enumerate().take(128) {
+        let control = controls.lines.contains_key(&char::from(index as u8).to_string());
+
+        if !control && lemma.as_bytes() != [index as u8] {
+            return Err(format!("{}:{}: {:?} must be {:?}, as ascii characters are stored at their own position", obw.path, index + 1, lemma, char::from(index as u8)));
+        }
+    }
+
+    //Two byte and three byte entries can repeat a one byte wonder, as they are still matched with a leading space.
+    //Three byte entries are only tried after two byte entries of the same length though, so one in both lists would never be used
+    tbu.check_disjoint(tbc, &KNOWN_OVERLAPS, "so it would never be used")?;
+
+    Ok(())
+}
 
-    let s = read_to_string(path.as_ref()).unwrap();
+fn hash_generate_list(list: &List, name: &str, code: & mut String, all_lengths: & mut HashSet<usize>, fingerprint: & mut u32) -> HashSet<usize> {
+    use std::fmt::Write;
 
     //Populate the lengths set with the lengths of all the lemmas in the list, and the phf builder with all (lemma, index) pairs
     let mut lengths = HashSet::new();
     let mut builder = phf_codegen::OrderedMap::new();
     let mut count = 0;
 
-    let mut slices = Vec::new();
-
-    for lemma in s.lines() {
-        slices.push(percent_encoding::percent_decode_str(lemma).decode_utf8().unwrap());
-    }
+    for  (i, lemma) in list.entries.iter().enumerate() {
+        //Each lemma is followed by 0xff, which can't appear in utf-8, so the fingerprint changes if lemmas are moved between entries
+        *fingerprint = fnv1a(*fingerprint, lemma.as_bytes());
+        *fingerprint = fnv1a(*fingerprint, &[0xff]);
 
-    for  (i, lemma) in slices.iter().enumerate() {
         builder.entry(lemma.as_bytes(), i.to_string().as_str());
         lengths.insert(lemma.len());
         all_lengths.insert(lemma.len());
@@ -31,6 +129,8 @@ fn hash_generate_list<P: AsRef<Path>>(path: P, name: &str, code: & mut String, a
     write!(code, "
 pub (crate) struct {};
 
+//Not every list uses every function
+#[allow(dead_code)]
 impl {} {{
 
     pub (crate) const fn get_length() -> usize {{
@@ -38,7 +138,11 @@ impl {} {{
     }}
 
     pub (crate) fn get_index(index: usize) -> & 'static str {{
-        unsafe {{ std::str::from_utf8_unchecked(Self::get_map().index(index).unwrap().0) }} //This unsafe is justified as the build script checks the lists for invalid utf-8 strings
+        Self::get(index).unwrap()
+    }}
+
+    pub (crate) fn get(index: usize) -> Option<& 'static str> {{
+        Self::get_map().index(index).map(|(lemma, _)| unsafe {{ core::str::from_utf8_unchecked(lemma) }}) //This unsafe is justified as the build script checks the lists for invalid utf-8 strings
     }}
 
     pub (crate) fn get_map() -> & 'static phf::OrderedMap<&'static [u8], usize> {{
@@ -51,32 +155,84 @@ impl {} {{
 
 }}", name, name, count, builder.build()).unwrap();
 
+    //Mark the end of the list, so the fingerprint changes if lemmas are moved between lists
+    *fingerprint = fnv1a(*fingerprint, &[0xfe]);
+
     lengths
 }
 
+//Languages with their own two byte and three byte lists. English uses the lists in ./lists, the others use the lists in ./lists/<language>
+const LANGUAGES: [&str; 4] = ["en", "de", "es", "fr"];
+
+//Chooses the language from the BYTE_SIZE_LANGUAGE environment variable, or the lang-* features if it is not set
+fn language() -> &'static str {
+    println!("cargo:rerun-if-env-changed=BYTE_SIZE_LANGUAGE");
+
+    if let Ok(language) = env::var("BYTE_SIZE_LANGUAGE") {
+        return LANGUAGES.iter().find(|l| **l == language).unwrap_or_else(|| panic!("Unknown language '{}' in BYTE_SIZE_LANGUAGE, expected one of {:?}", language, LANGUAGES));
+    }
+
+    let enabled: Vec<_> = LANGUAGES[1..].iter().filter(|l| env::var_os(format!("CARGO_FEATURE_LANG_{}", l.to_uppercase())).is_some()).collect();
+
+    //Features are additive, so several languages can be enabled at once (for example with --all-features)
+    if enabled.len() > 1 {
+        println!("cargo:warning=Several language features are enabled ({:?}), using '{}'. Set BYTE_SIZE_LANGUAGE to choose a different one", enabled, enabled[0]);
+    }
+
+    enabled.first().copied().unwrap_or(&LANGUAGES[0])
+}
+
 fn main() {
     use std::fmt::Write;
 
+    let language = language();
+
+    let lists = if language == LANGUAGES[0] { String::from("./lists") } else { format!("./lists/{}", language) };
+
     println!("cargo:rerun-if-changed=./lists/obw.txt");
-    println!("cargo:rerun-if-changed=./lists/tbc.txt");
-    println!("cargo:rerun-if-changed=./lists/tbu.txt");
+    println!("cargo:rerun-if-changed={}/tbc.txt", lists);
+    println!("cargo:rerun-if-changed={}/tbu.txt", lists);
     println!("cargo:rerun-if-changed=./lists/controls.txt");
     println!("cargo:rerun-if-changed=./lists/repetitions.txt");
+    println!("cargo:rerun-if-changed=./src/fnv.rs");
+    println!("cargo:rerun-if-changed=./src/overlaps.rs");
+
+    println!("cargo:rustc-check-cfg=cfg(byte_size_language, values(\"en\", \"de\", \"es\", \"fr\"))");
+    println!("cargo:rustc-cfg=byte_size_language=\"{}\"", language);
+
+    let read = || -> Result<_, String> {
+        let obw = List::read(String::from("./lists/obw.txt"))?;
+        let tbc = List::read(format!("{}/tbc.txt", lists))?;
+        let tbu = List::read(format!("{}/tbu.txt", lists))?;
+        let controls = List::read(String::from("./lists/controls.txt"))?;
+        let repetitions = List::read(String::from("./lists/repetitions.txt"))?;
+
+        validate(&obw, &tbc, &tbu, &controls, &repetitions)?;
+
+        Ok((obw, tbc, tbu, controls, repetitions))
+    };
+
+    //A panic would bury the message in a backtrace, so print it and fail the build
+    let (obw, tbc, tbu, controls, repetitions) = read().unwrap_or_else(|message| {
+        eprintln!("error: {}", message);
+        std::process::exit(1);
+    });
 
     let mut all_lengths = HashSet::new();
+    let mut fingerprint = 0x811c9dc5u32;
 
     //Here we take the two_byte_common.txt and three_byte_uncommon.txt files and convert them into phf tables
     let mut code = String::new();
 
-    hash_generate_list("./lists/tbc.txt", "TwoByteMap", & mut code, & mut all_lengths);
+    hash_generate_list(&tbc, "TwoByteMap", & mut code, & mut all_lengths, & mut fingerprint);
 
-    hash_generate_list("./lists/tbu.txt", "ThreeByteMap", & mut code, & mut all_lengths);
+    hash_generate_list(&tbu, "ThreeByteMap", & mut code, & mut all_lengths, & mut fingerprint);
 
-    hash_generate_list("./lists/obw.txt", "OneByteMap", & mut code, & mut all_lengths);
+    hash_generate_list(&obw, "OneByteMap", & mut code, & mut all_lengths, & mut fingerprint);
 
-    hash_generate_list("./lists/controls.txt", "Controls", & mut code, & mut all_lengths);
+    hash_generate_list(&controls, "Controls", & mut code, & mut all_lengths, & mut fingerprint);
 
-    let rep_lengths = hash_generate_list("./lists/repetitions.txt", "Repetitions", & mut code, & mut all_lengths);
+    let rep_lengths = hash_generate_list(&repetitions, "Repetitions", & mut code, & mut all_lengths, & mut fingerprint);
 
     let mut rep_lengths: Vec<_> = rep_lengths.iter().collect();
     rep_lengths.sort();
@@ -103,7 +259,11 @@ fn main() {
         write!(& mut code, "{}usize, ", *length).unwrap();
     }
 
-    write!(& mut code, "];").unwrap();
+    write!(& mut code, "];\n\n").unwrap();
+
+    write!(& mut code, "pub (crate) const TABLE_FINGERPRINT: u32 = {};\n\n", fingerprint).unwrap();
+
+    write!(& mut code, "///Language of the two byte and three byte lists built into the crate, chosen with the `lang-*` features or the `BYTE_SIZE_LANGUAGE` environment variable\npub const LANGUAGE: & str = {:?};", language).unwrap();
 
     let mut fs = File::create(Path::new(&env::var("OUT_DIR").unwrap()).join("maps.rs")).unwrap();
 
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use byte_size::builder::Builder;
+
+//Decompressing arbitrary bytes must either succeed or return an error, but never panic
+fuzz_target!(|data: &[u8]| {
+    let _ = byte_size::decompress(data);
+
+    let _ = Builder::empty().engine().decompress(data);
+
+    let _ = Builder::default().set_custom_spaces(true).engine().decompress(data);
+});
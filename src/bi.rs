@@ -1,4 +1,5 @@
 
+use alloc::vec::Vec;
 
 //Convenient struct containing information on a match, including the index of the match (in whatever map it matched), the length of the match and whether a space was matched too
 #[derive(PartialEq, Debug, Clone)]
@@ -26,10 +27,8 @@ pub (crate) trait Bi {
     }
 
     fn match_spaced_sequence(&self, string: &[u8], space: bool, length: usize) -> Option<Match> {
-        if string.len() >= length + 1 {
-            if space && string[0] == ' ' as u8 {
-                return self.match_sequence(string, length, 1, true);
-            }
+        if string.len() > length && space && string[0] == b' ' {
+            return self.match_sequence(string, length, 1, true);
         }
 
         if string.len() >= length {
@@ -38,6 +37,21 @@ pub (crate) trait Bi {
 
         None
     }
+
+    //Unlike match_spaced_sequence, this returns both the spaced and unspaced matches (if they exist)
+    fn match_all_sequences(&self, string: &[u8], space: bool, length: usize) -> Vec<Match> {
+        let mut matches = Vec::new();
+
+        if string.len() > length && space && string[0] == b' ' {
+            matches.extend(self.match_sequence(string, length, 1, true));
+        }
+
+        if string.len() >= length {
+            matches.extend(self.match_sequence(string, length, 0, false));
+        }
+
+        matches
+    }
 }
 
 impl Bi for phf::OrderedMap<& 'static [u8], usize> {
@@ -46,7 +60,7 @@ impl Bi for phf::OrderedMap<& 'static [u8], usize> {
     }
 }
 
-impl Bi for bimap::BiHashMap<& 'static [u8], usize> {
+impl Bi for bimap::BiBTreeMap<Vec<u8>, usize> {
     fn get(&self, string: & [u8]) -> Option<usize> {
         self.get_by_left(string).copied()
     }
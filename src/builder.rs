@@ -1,6 +1,25 @@
-use std::collections::{HashSet};
-use bimap::BiHashMap;
+use alloc::borrow::Cow;
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bimap::BiBTreeMap;
 use crate::engine::Engine;
+use crate::tables::Tables;
+
+///Determines how the compressor chooses which codes to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseStrategy {
+    ///Picks the first (and usually longest) code that matches at each position.
+    ///
+    /// This is fast, but does not always give the smallest output
+    #[default]
+    Greedy,
+
+    ///Considers every code that matches at every position and picks the combination that gives the smallest output.
+    ///
+    /// The output is the smallest split of the string into the codes it tries, which are every match in the tables, custom list and repetitions and every prefix of a number, hex run or date. Compression is much slower
+    Optimal,
+}
 
 ///Compress/decompress with specific options
 ///
@@ -8,15 +27,21 @@ use crate::engine::Engine;
 ///
 /// If you're not sure about the options, either use [Builder::default] or use the convenience functions [Engine::compress] and [Engine::decompress]
 pub struct Builder {
-    custom: Vec<& 'static str>,
+    custom: Vec<Cow<'static, str>>,
     custom_spaces: bool,
+    parse_strategy: ParseStrategy,
+    header: bool,
+    tables: Arc<Tables>,
 }
 
 impl Default for Builder {
     fn default() -> Self {
         Self {
-            custom: vec!["http://", "https://", ".com", "\n\r\n", "\r\n\r", "C:\\", ".co.uk"],
+            custom: ["http://", "https://", ".com", "\n\r\n", "\r\n\r", "C:\\", ".co.uk"].into_iter().map(Cow::Borrowed).collect(),
             custom_spaces: false,
+            parse_strategy: ParseStrategy::Greedy,
+            header: false,
+            tables: Arc::new(Tables::builtin()),
         }
     }
 }
@@ -28,6 +53,9 @@ impl Builder {
         Self {
             custom: Vec::new(),
             custom_spaces: false,
+            parse_strategy: ParseStrategy::Greedy,
+            header: false,
+            tables: Arc::new(Tables::builtin()),
         }
     }
 
@@ -36,11 +64,16 @@ impl Builder {
     ///Move in a new list of custom words
     ///
     /// sss supports the use of 32 custom strings which are encoded as two bytes. This list can be replaced completely with this [Builder::set_custom] function,
-    /// or it can be modified with the [Builder::push_custom] or [Builder::clear_custom] functions.
+    /// or it can be modified with the [Builder::push_custom], [Builder::extend_custom] or [Builder::clear_custom] functions.
+    ///
+    /// The list can contain string literals or owned `String`s (such as strings loaded from a config file), which are owned by the resulting [Engine].
+    ///
+    /// Empty strings are never used, but still take up their index so the strings after them keep the same codes.
     ///
     /// Note: The protocol only supports 32 custom strings, so only the first 32 strings will be used in the custom vector. Adding more than 32 is not an error, but these extra strings will not be used.
-    pub fn set_custom(& mut self, list: Vec<& 'static str>) -> & mut Self {
-        self.custom = list;
+    pub fn set_custom<I, S>(& mut self, list: I) -> & mut Self
+        where I: IntoIterator<Item = S>, S: Into<Cow<'static, str>> {
+        self.custom = list.into_iter().map(Into::into).collect();
         self
     }
 
@@ -52,9 +85,42 @@ impl Builder {
         self
     }
 
+    ///Sets the strategy used to choose codes when compressing. See [ParseStrategy] for more information.
+    ///
+    /// The strategy only affects compression, any engine can decompress the output regardless of the strategy used.
+    pub fn set_parse_strategy(& mut self, strategy: ParseStrategy) -> & mut Self {
+        self.parse_strategy = strategy;
+        self
+    }
+
+    ///Determines whether compressed bytes start with a header.
+    ///
+    /// The header is 5 bytes long and contains the version of the compressed format along with a fingerprint of the tables, custom list and custom spaces option.
+    /// When decompressing, the header is checked against the engine and an error is returned if they disagree, instead of silently returning the wrong string.
+    /// Both the compressing and decompressing engines must have the header enabled.
+    pub fn set_header(& mut self, header: bool) -> & mut Self {
+        self.header = header;
+        self
+    }
+
+    ///Sets the one byte wonder, two byte common and three byte uncommon lists. By default the lists built into the crate are used.
+    ///
+    /// Tables can be shared between builders (and the engines they create) by passing an `Arc<Tables>`. See [Tables] for more information.
+    pub fn set_tables<T: Into<Arc<Tables>>>(& mut self, tables: T) -> & mut Self {
+        self.tables = tables.into();
+        self
+    }
+
     ///Appends a single string to the custom list. See the [Builder::set_custom] for more information on custom strings.
-    pub fn push_custom(& mut self, custom: & 'static str) -> & mut Self {
-        self.custom.push(custom);
+    pub fn push_custom<S: Into<Cow<'static, str>>>(& mut self, custom: S) -> & mut Self {
+        self.custom.push(custom.into());
+        self
+    }
+
+    ///Appends each string in the iterator to the custom list. See the [Builder::set_custom] for more information on custom strings.
+    pub fn extend_custom<I, S>(& mut self, list: I) -> & mut Self
+        where I: IntoIterator<Item = S>, S: Into<Cow<'static, str>> {
+        self.custom.extend(list.into_iter().map(Into::into));
         self
     }
 
@@ -74,18 +140,25 @@ impl Builder {
 
         let max_len = if self.custom_spaces { 16 } else { 32 };
 
-        let v = if self.custom.len() <= max_len { self.custom.clone() } else { (&self.custom[0..max_len]).to_vec() };
+        let custom: Vec<_> = self.custom.iter().take(max_len).collect();
+
+        let mut map = BiBTreeMap::new();
+        let mut lengths = BTreeSet::new();
 
-        let mut map = BiHashMap::new();
-        let mut lengths = HashSet::new();
+        for (i, string) in custom.iter().enumerate() {
+            //Empty strings can never be matched, but they keep their index so the strings after them keep their codes
+            if string.is_empty() {
+                continue;
+            }
 
-        for (i, string) in v.iter().enumerate() {
-            map.insert(string.as_bytes(), i);
+            map.insert(string.as_bytes().to_vec(), i);
             lengths.insert(string.len());
 
         }
 
-        let mut lengths: Vec<_> = lengths.iter().map(|x| *x).collect();
+        let fingerprint = crate::header::engine_fingerprint(self.tables.fingerprint, custom.iter().map(|s| s.as_bytes()), self.custom_spaces);
+
+        let mut lengths: Vec<_> = lengths.iter().copied().collect();
         lengths.sort();
         lengths.reverse();
 
@@ -93,6 +166,10 @@ impl Builder {
             custom_spaces: self.custom_spaces,
             custom_map: map,
             lengths,
+            parse_strategy: self.parse_strategy,
+            header: self.header,
+            fingerprint,
+            tables: self.tables.clone(),
         }
     }
 
@@ -0,0 +1,156 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display, Formatter};
+use crate::cursor::Cursor;
+use crate::engine::Engine;
+use crate::error::Result;
+
+///A string stored compressed, using the [Builder::default](crate::builder::Builder::default) options.
+///
+/// This is useful for keeping many short strings in memory. The string is only decompressed when it is needed, for example by [Display], [CompressedStr::decompress] or `to_string`.
+///
+/// Two `CompressedStr`s are equal (and hash the same) exactly when their strings are equal, as the compressed bytes of a string are always the same.
+/// With the `serde` feature, `CompressedStr` is serialized as plain text by human readable formats (such as JSON), and as the compressed bytes by binary formats.
+///
+/// ```
+/// use byte_size::compressed::CompressedStr;
+///
+/// let s = CompressedStr::from("the quick brown fox");
+///
+/// assert!(s.as_bytes().len() < "the quick brown fox".len());
+/// assert_eq!(s.to_string(), "the quick brown fox");
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct CompressedStr {
+    bytes: Box<[u8]>,
+}
+
+impl CompressedStr {
+
+    ///Compresses the string
+    pub fn new(string: & str) -> Self {
+        Self { bytes: Engine::default_ref().compress(string).into_boxed_slice() }
+    }
+
+    ///Creates a `CompressedStr` from bytes returned by [CompressedStr::as_bytes] (or compressed with the [Builder::default](crate::builder::Builder::default) options).
+    ///
+    /// Returns an error if the bytes do not decompress. Bytes compressed by an older version of the crate are compressed again, so equality still matches the strings.
+    pub fn from_bytes(bytes: & [u8]) -> Result<Self> {
+        let engine = Engine::default_ref();
+        let string = engine.decompress(bytes)?;
+        let compressed = engine.compress(&string);
+
+        //Only the compressed bytes are compared, so they must be the ones this version gives for the string
+        Ok(Self { bytes: if compressed == bytes { Box::from(bytes) } else { compressed.into_boxed_slice() } })
+    }
+
+    ///Gets the compressed bytes
+    pub fn as_bytes(&self) -> & [u8] {
+        &self.bytes
+    }
+
+    ///Gets the compressed bytes, consuming the `CompressedStr`
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes.into_vec()
+    }
+
+    ///Decompresses the string
+    pub fn decompress(&self) -> String {
+        let mut string = String::new();
+
+        //The bytes were either compressed or checked when the CompressedStr was created, so this cannot fail
+        Engine::default_ref().decompress_into(&self.bytes, & mut string).expect("CompressedStr holds invalid bytes");
+
+        string
+    }
+}
+
+impl From<& str> for CompressedStr {
+    fn from(string: & str) -> Self {
+        Self::new(string)
+    }
+}
+
+impl From<String> for CompressedStr {
+    fn from(string: String) -> Self {
+        Self::new(&string)
+    }
+}
+
+impl From<CompressedStr> for String {
+    fn from(string: CompressedStr) -> Self {
+        string.decompress()
+    }
+}
+
+impl Display for CompressedStr {
+    fn fmt(&self, f: & mut Formatter<'_>) -> core::fmt::Result {
+        //Padding and truncation need the whole string, otherwise decompress straight into the formatter, so no String is allocated
+        if f.width().is_some() || f.precision().is_some() {
+            return f.pad(&self.decompress());
+        }
+
+        Engine::default_ref().decode(& mut Cursor::new(&self.bytes, 0), f).map_err(|_| core::fmt::Error)
+    }
+}
+
+impl Debug for CompressedStr {
+    fn fmt(&self, f: & mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("CompressedStr").field(&self.decompress()).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for CompressedStr {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.decompress())
+        } else {
+            serializer.serialize_bytes(&self.bytes)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for CompressedStr {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
+        }
+    }
+}
+
+//Accepts either plain text or compressed bytes, whichever the format gives
+#[cfg(feature = "serde")]
+struct Visitor;
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::de::Visitor<'de> for Visitor {
+    type Value = CompressedStr;
+
+    fn expecting(&self, f: & mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("a string or compressed bytes")
+    }
+
+    fn visit_str<E: ::serde::de::Error>(self, string: & str) -> core::result::Result<Self::Value, E> {
+        Ok(CompressedStr::new(string))
+    }
+
+    fn visit_bytes<E: ::serde::de::Error>(self, bytes: & [u8]) -> core::result::Result<Self::Value, E> {
+        CompressedStr::from_bytes(bytes).map_err(E::custom)
+    }
+
+    //Some formats (such as JSON) can only store bytes as a sequence of numbers
+    fn visit_seq<A: ::serde::de::SeqAccess<'de>>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+
+        self.visit_bytes(&bytes)
+    }
+}
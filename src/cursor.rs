@@ -0,0 +1,80 @@
+use crate::error::{Error, Result};
+
+//Source of compressed bytes for the deserializer. Implemented for byte slices by Cursor, and for std::io::Read by IoSource (with the std feature)
+pub (crate) trait Source {
+
+    //Reads the next byte, or None if there are no bytes left
+    fn next_byte(& mut self) -> Result<Option<u8>>;
+
+    //Number of bytes read so far, used to report where errors happen
+    fn position(&self) -> usize;
+
+    //Reads a byte that must be present (as it is part of a code)
+    fn read_byte(& mut self) -> Result<u8> {
+        self.next_byte()?.ok_or(Error::UnexpectedEndOfBytes)
+    }
+
+    //Reads the rest of a single utf-8 encoded unicode scalar value, whose first byte has already been read
+    fn read_char_from(& mut self, first: u8) -> Result<char> {
+        let mut sequence = [first, 0, 0, 0];
+
+        let width = utf8_width(sequence[0]).ok_or(Error::InvalidUnicodeChar)?;
+
+        for byte in sequence[1..width].iter_mut() {
+            *byte = self.read_byte()?;
+        }
+
+        parse_char(&sequence[..width])
+    }
+}
+
+//Reads bytes directly from a slice, without copying
+pub (crate) struct Cursor<'a> {
+    bytes: & 'a [u8],
+    position: usize,
+    start: usize,
+}
+
+impl<'a> Cursor<'a> {
+
+    //Errors are reported relative to `start`, for when the slice is part of a larger buffer
+    pub (crate) fn new(bytes: & 'a [u8], start: usize) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            start,
+        }
+    }
+}
+
+//Number of bytes in the utf-8 sequence starting with `first`, or None if `first` can't start a sequence
+fn utf8_width(first: u8) -> Option<usize> {
+    match first {
+        0x00..=0x7f => Some(1),
+        0xc2..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf4 => Some(4),
+        _ => None,
+    }
+}
+
+//Parses a complete utf-8 sequence holding exactly one unicode scalar value
+fn parse_char(sequence: & [u8]) -> Result<char> {
+    core::str::from_utf8(sequence).map_err(|_| Error::InvalidUnicodeChar)?.chars().next().ok_or(Error::InvalidUnicodeChar)
+}
+
+impl Source for Cursor<'_> {
+    fn next_byte(& mut self) -> Result<Option<u8>> {
+        let byte = self.bytes.get(self.position).copied();
+
+        if byte.is_some() {
+            self.position += 1;
+        }
+
+        Ok(byte)
+    }
+
+    fn position(&self) -> usize {
+        self.start + self.position
+    }
+}
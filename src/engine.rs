@@ -1,54 +1,369 @@
-use bimap::BiHashMap;
-use crate::builder::Builder;
-use crate::iterator::CodeIterator;
-use crate::error::Result;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bimap::BiBTreeMap;
+use crate::builder::{Builder, ParseStrategy};
+use crate::iterator::{CodeIterator, Codes};
+use crate::error::{Error, Result};
 use crate::ir::CodeType;
+use crate::cursor::{Cursor, Source};
+use crate::framed::FramedReader;
+use crate::explain::Token;
+use crate::tables::Tables;
 
 ///Used to compress and decompress
 ///
-/// Can only be created with the [Builder] struct via [Builder::engine]
+/// Can only be created with the [Builder] struct via [Builder::engine], or shared with [Engine::default_ref].
+///
+/// Engines are `Send` and `Sync`, so one engine can be shared between threads (for example in an `Arc`) rather than building one per thread or per string.
+/// Cloning an engine is cheap compared to building one, as the tables are shared.
+#[derive(Clone)]
 pub struct Engine {
     pub (crate) custom_spaces: bool,
-    pub (crate) custom_map: BiHashMap<& 'static [u8], usize>,
+    pub (crate) custom_map: BiBTreeMap<Vec<u8>, usize>,
     pub (crate) lengths: Vec<usize>,
+    pub (crate) parse_strategy: ParseStrategy,
+    pub (crate) header: bool,
+    pub (crate) fingerprint: u32,
+    pub (crate) tables: Arc<Tables>,
 }
 
+//The engine returned by Engine::default_ref, which is built the first time it is used.
+//Without std this is a spin lock, which only needs the atomics also needed by Arc
+#[cfg(feature = "std")]
+static DEFAULT: std::sync::OnceLock<Engine> = std::sync::OnceLock::new();
+#[cfg(not(feature = "std"))]
+static DEFAULT: spin::Once<Engine> = spin::Once::new();
+
 impl Engine {
 
+    ///Returns an engine using the [Builder::default] options, shared by the whole program.
+    ///
+    /// The engine is built the first time this is called, so later calls (and the [compress] and [decompress] functions) don't pay for building one.
+    pub fn default_ref() -> & 'static Engine {
+        #[cfg(feature = "std")]
+        return DEFAULT.get_or_init(|| Builder::default().engine());
+
+        #[cfg(not(feature = "std"))]
+        DEFAULT.call_once(|| Builder::default().engine())
+    }
+
+    //Gets the codes for the bytes using the engine's parse strategy. If `utf8` is true the bytes must be valid utf-8, otherwise bytes that aren't are encoded as raw bytes
+    pub (crate) fn codes<'a>(&'a self, bytes: & 'a [u8], utf8: bool) -> Codes<'a> {
+        match self.parse_strategy {
+            ParseStrategy::Greedy => Codes::Greedy(CodeIterator::new(bytes, utf8, self)),
+            ParseStrategy::Optimal => Codes::Optimal(crate::optimal::optimal_codes(bytes, utf8, self).into_iter()),
+        }
+    }
+
     ///Compress the string using the builder options
     pub fn compress(&self, string: & str) -> Vec<u8> {
         let mut res = Vec::new();
 
-        for code in CodeIterator::new(string, &self) {
-            code.serialize_into(& mut res, &self);
+        self.compress_to_vec(string, & mut res);
+
+        res
+    }
+
+    ///Compress the string into a writer, returning the number of bytes written.
+    ///
+    /// Any error raised by the writer is returned as is.
+    #[cfg(feature = "std")]
+    pub fn compress_into<W: std::io::Write>(&self, string: & str, writer: & mut W) -> std::io::Result<usize> {
+        let mut written = 0;
+
+        if self.header {
+            let header = crate::header::header_bytes(self);
+            writer.write_all(&header)?;
+            written += header.len();
         }
 
+        for code in self.codes(string.as_bytes(), true) {
+            for part in code.to_bytes(self).parts() {
+                writer.write_all(part)?;
+                written += part.len();
+            }
+        }
+
+        Ok(written)
+    }
+
+    ///Compress the string, appending the result to the end of `bytes` and returning the number of bytes appended.
+    ///
+    /// Unlike [Engine::compress] this does not allocate, so long as `bytes` has enough spare capacity.
+    pub fn compress_to_vec(&self, string: & str, bytes: & mut Vec<u8>) -> usize {
+        self.encode_to_vec(string.as_bytes(), true, bytes)
+    }
+
+    ///Compress a byte slice that may not be valid utf-8 (for example Latin-1 text, or identifiers from other systems), using the builder options.
+    ///
+    /// Valid utf-8 is compressed exactly as [Engine::compress] would, and each byte that isn't takes two or three bytes.
+    /// The result must be decompressed with [Engine::decompress_bytes]. [Engine::decompress] returns [Error::InvalidUtf8] if there were any bytes that aren't valid utf-8.
+    pub fn compress_bytes(&self, bytes: & [u8]) -> Vec<u8> {
+        let mut res = Vec::new();
+
+        self.encode_to_vec(bytes, false, & mut res);
+
         res
     }
 
+    //Appends the header (if enabled) and the codes for the bytes to `output`, returning the number of bytes appended
+    fn encode_to_vec(&self, bytes: & [u8], utf8: bool, output: & mut Vec<u8>) -> usize {
+        let start = output.len();
+
+        if self.header {
+            output.extend_from_slice(&crate::header::header_bytes(self));
+        }
+
+        for code in self.codes(bytes, utf8) {
+            for part in code.to_bytes(self).parts() {
+                output.extend_from_slice(part);
+            }
+        }
+
+        output.len() - start
+    }
+
     ///Tries to decompress the byte slice.
     ///
     /// If successful, the decompressed string is returned. Otherwise a [Result] is returned.
-    pub fn decompress(&self, mut bytes: & [u8]) -> Result<String> {
+    ///
+    /// Malformed bytes never cause a panic, so it is safe to decompress untrusted data.
+    pub fn decompress(&self, bytes: & [u8]) -> Result<String> {
         let mut string = String::new();
 
-        while !bytes.is_empty() {
-            let code = CodeType::deserialize_from(& mut bytes, &self)?;
+        self.decompress_into(bytes, & mut string)?;
+
+        Ok(string)
+    }
+
+    ///Tries to decompress the byte slice, appending the result to the end of `string`.
+    ///
+    /// This allows the same `String` to be reused when decompressing many byte slices.
+    /// If an error is returned, `string` may contain some (but not all) of the decompressed string.
+    pub fn decompress_into(&self, bytes: & [u8], string: & mut String) -> Result<()> {
+        self.decode(& mut Cursor::new(bytes, 0), string)
+    }
+
+    ///Tries to decompress every byte in the reader, writing the result into `writer`.
+    ///
+    /// Decompression stops when the reader reaches the end of the stream. Errors from the reader are returned as [Error::Io].
+    /// If an error is returned, `writer` may contain some (but not all) of the decompressed string.
+    #[cfg(feature = "std")]
+    pub fn decompress_from<R: std::io::Read, W: core::fmt::Write>(&self, reader: R, writer: & mut W) -> Result<()> {
+        self.decode(& mut crate::serde::IoSource::new(reader, 0), writer)
+    }
+
+    ///Tries to decompress bytes created with [Engine::compress_bytes] (or [Engine::compress]).
+    pub fn decompress_bytes(&self, bytes: & [u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+
+        self.decode_codes(& mut Cursor::new(bytes, 0), |code, offset| {
+            match code {
+                CodeType::RawByte(byte) => output.push(byte),
+                code => code.add_to_string(& mut ByteWriter(& mut output), self, offset)?,
+            }
 
-            code.add_to_string(& mut string, &self)?;
+            Ok(())
+        })?;
+
+        Ok(output)
+    }
+
+    //Decodes every code in the source, writing the result into `writer`
+    pub (crate) fn decode<S: Source, W: core::fmt::Write>(&self, source: & mut S, writer: & mut W) -> Result<()> {
+        self.decode_codes(source, |code, offset| code.add_to_string(writer, self, offset))
+    }
+
+    //Reads every code in the source, passing each one to `f` along with its offset
+    fn decode_codes<S: Source, F: FnMut(CodeType, usize) -> Result<()>>(&self, source: & mut S, mut f: F) -> Result<()> {
+        if self.header {
+            crate::header::read_header(source, self)?;
         }
 
-        Ok(string)
+        loop {
+            let offset = source.position();
+
+            let first = match source.next_byte()? {
+                Some(first) => first,
+                None => break,
+            };
+
+            let code = CodeType::deserialize_from(first, source, self, offset)?;
+
+            f(code, offset)?;
+        }
+
+        Ok(())
+    }
+
+    ///Lists the codes used to compress the string, along with the part of the string each one encodes.
+    ///
+    /// This is useful for finding out why a string compressed poorly, or for choosing custom strings.
+    /// Concatenating the bytes of every token gives the same result as [Engine::compress], except for the header (if enabled).
+    pub fn explain(&self, string: & str) -> Vec<Token> {
+        let mut start = 0;
+
+        self.codes(string.as_bytes(), true).map(|code| {
+            let token = Token::new(&code, start, self);
+            start = token.span.end;
+            token
+        }).collect()
+    }
+
+    ///Returns the fingerprint of the tables, custom list and custom spaces option used by this engine.
+    ///
+    /// Engines with the same fingerprint produce and accept the same compressed bytes. If the header is enabled (see [Builder::set_header]) this is stored in the header.
+    pub fn fingerprint(&self) -> u32 {
+        self.fingerprint
+    }
+
+    ///Returns the tables used by this engine
+    pub fn tables(&self) -> & Tables {
+        &self.tables
+    }
+
+    ///Compress the string into a self-delimiting record, which is the compressed bytes prefixed with their length.
+    ///
+    /// Unlike [Engine::compress], records can be concatenated into a single buffer and separated again with a [FramedReader].
+    /// The length is stored as a variable length integer, so records shorter than 128 bytes only take one extra byte.
+    pub fn compress_framed(&self, string: & str) -> Vec<u8> {
+        let mut res = Vec::new();
+
+        self.compress_framed_to_vec(string, & mut res);
+
+        res
+    }
+
+    ///Compress the string into a self-delimiting record (see [Engine::compress_framed]), appending it to the end of `bytes` and returning the number of bytes appended.
+    pub fn compress_framed_to_vec(&self, string: & str, bytes: & mut Vec<u8>) -> usize {
+        let start = bytes.len();
+
+        let length = self.compress_to_vec(string, bytes);
+
+        //We only know the length once the string is compressed, so the prefix is inserted before the record afterwards
+        let prefix = crate::framed::encode_length(length);
+
+        bytes.splice(start..start, prefix.iter().copied());
+
+        length + prefix.len()
     }
 
+    ///Returns an iterator over the strings stored in a buffer of concatenated records created with [Engine::compress_framed]
+    pub fn framed_reader<'a>(&'a self, bytes: & 'a [u8]) -> FramedReader<'a> {
+        FramedReader::new(bytes, self)
+    }
+
+    ///Compresses every string into one buffer, returning the buffer and the offsets of the strings in it.
+    ///
+    /// There is one more offset than there are strings: string `i` is stored in `bytes[offsets[i]..offsets[i + 1]]`, and the first offset is always 0.
+    /// This avoids allocating a `Vec` per string when compressing many strings (for example a column of a table), and the result can be decompressed with [Engine::decompress_batch].
+    ///
+    /// With the `rayon` feature the strings are compressed on several threads. The bytes are the same either way.
+    ///
+    /// Panics if the compressed strings take up more than `u32::MAX` bytes, so very large batches should be split up.
+    pub fn compress_batch<S: AsRef<str> + Sync>(&self, strings: & [S]) -> (Vec<u8>, Vec<u32>) {
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::with_capacity(strings.len() + 1);
+
+        offsets.push(0);
+
+        #[cfg(not(feature = "rayon"))]
+        self.compress_chunk(strings, & mut bytes, & mut offsets);
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            //Each thread compresses a whole chunk into its own buffer, so only the chunks need joining afterwards
+            let chunks: Vec<_> = strings.par_chunks(BATCH_CHUNK_LENGTH).map(|chunk| {
+                let mut chunk_bytes = Vec::new();
+                let mut chunk_offsets = Vec::with_capacity(chunk.len());
+
+                self.compress_chunk(chunk, & mut chunk_bytes, & mut chunk_offsets);
+
+                (chunk_bytes, chunk_offsets)
+            }).collect();
+
+            for (chunk_bytes, chunk_offsets) in chunks {
+                let start = bytes.len();
+
+                bytes.extend_from_slice(&chunk_bytes);
+                offsets.extend(chunk_offsets.into_iter().map(|offset| batch_offset(start + offset as usize)));
+            }
+        }
+
+        (bytes, offsets)
+    }
+
+    //Compresses the strings onto the end of `bytes`, pushing the end offset of each one
+    fn compress_chunk<S: AsRef<str>>(&self, strings: & [S], bytes: & mut Vec<u8>, offsets: & mut Vec<u32>) {
+        for string in strings {
+            self.compress_to_vec(string.as_ref(), bytes);
+            offsets.push(batch_offset(bytes.len()));
+        }
+    }
+
+    ///Decompresses every string in a buffer created with [Engine::compress_batch].
+    ///
+    /// Returns [Error::InvalidBatchOffset] if the offsets are not in order or point past the end of the bytes, or the first error found while decompressing.
+    /// Error offsets are positions in the whole buffer, not in the string.
+    ///
+    /// With the `rayon` feature the strings are decompressed on several threads.
+    pub fn decompress_batch(&self, bytes: & [u8], offsets: & [u32]) -> Result<Vec<String>> {
+        let decompress = |index: usize| -> Result<String> {
+            let start = offsets[index] as usize;
+            let end = offsets[index + 1] as usize;
+
+            let record = bytes.get(start..end).ok_or(Error::InvalidBatchOffset { index: index + 1 })?;
+
+            let mut string = String::new();
+
+            self.decode(& mut Cursor::new(record, start), & mut string)?;
+
+            Ok(string)
+        };
+
+        let count = offsets.len().saturating_sub(1);
+
+        #[cfg(not(feature = "rayon"))]
+        return (0..count).map(decompress).collect();
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            (0..count).into_par_iter().with_min_len(BATCH_CHUNK_LENGTH).map(decompress).collect()
+        }
+    }
+
+}
+
+//Lets codes write their text into a byte vector
+struct ByteWriter<'a>(& 'a mut Vec<u8>);
+
+impl core::fmt::Write for ByteWriter<'_> {
+    fn write_str(& mut self, s: & str) -> core::fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+//Number of strings each thread compresses or decompresses at a time in a batch. Short strings are quick to compress, so giving each thread one at a time would spend more time scheduling than compressing
+#[cfg(feature = "rayon")]
+const BATCH_CHUNK_LENGTH: usize = 1024;
+
+//Converts a position in a batch to an offset
+fn batch_offset(position: usize) -> u32 {
+    u32::try_from(position).expect("Compressed batch is larger than u32::MAX bytes")
 }
 
-///Convenience function to compress a string using the [Builder::default] options
+///Convenience function to compress a string using the [Builder::default] options, with the engine from [Engine::default_ref]
 pub fn compress(string: &str) -> Vec<u8> {
-    Builder::default().engine().compress(string)
+    Engine::default_ref().compress(string)
 }
 
-///Convenience function to decompress a byte slice using the [Builder::default] options
+///Convenience function to decompress a byte slice using the [Builder::default] options, with the engine from [Engine::default_ref]
 pub fn decompress(bytes: & [u8]) -> Result<String> {
-    Builder::default().engine().decompress(bytes)
+    Engine::default_ref().decompress(bytes)
 }
@@ -1,57 +1,167 @@
-use std::ops::Deref;
-use bincode::ErrorKind;
-use thiserror::Error;
+use core::fmt::{Display, Formatter};
 
 ///Result using the [enum@Error]
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 ///Enum representing decompression errors
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub enum Error {
 
-    ///Error created by the `write` macro from [std::fmt::Error] when converting a code into a string
+    ///Error created by the `write` macro from [core::fmt::Error] when converting a code into a string
     ///
-    /// (Not really sure if this is needed as the errors from std::fmt are not well defined
-    #[error("Error converting IR to string")]
-    Format(#[from] std::fmt::Error),
+    /// (Not really sure if this is needed as the errors from core::fmt are not well defined
+    Format(core::fmt::Error),
 
     ///Raised when the deserializer expects more bytes than it gets.
     ///
     /// For example, a code starting with 240 (unicode) expects at least one byte to follow. If it doesn't, this error will be raised.
-    #[error("Unexpected end of bytes. Deserialiser expected more bytes in the decompress slice")]
     UnexpectedEndOfBytes,
 
     ///If an invalid unicode sequence is detected by the deserializer
-    #[error("Could not deserialize invalid unicode scalar value")]
     InvalidUnicodeChar,
 
-    ///This error encompasses other [bincode] errors that are impossible or unlikely
-    #[error("Unexpected bincode error")]
-    OtherBincode(bincode::Error),
+    ///Raised when decompressing to a string finds a byte that isn't valid utf-8.
+    ///
+    /// These are only created by [Engine::compress_bytes](crate::engine::Engine::compress_bytes), so decompress them with [Engine::decompress_bytes](crate::engine::Engine::decompress_bytes) instead.
+    InvalidUtf8 {
+        ///Byte offset of the code in the compressed slice
+        offset: usize,
+    },
+
+    ///Raised when a custom code refers to an index that is not in the engine's custom list.
+    ///
+    /// This usually means the bytes were compressed with a different custom list, or have been corrupted.
+    InvalidCustomIndex {
+        ///The custom index found in the code
+        index: usize,
+        ///Byte offset of the code in the compressed slice
+        offset: usize,
+    },
+
+    ///Raised when a repetition code refers to a sequence past the end of the repetitions list
+    InvalidRepetitionIndex {
+        ///The repetition index found in the code
+        index: usize,
+        ///Byte offset of the code in the compressed slice
+        offset: usize,
+    },
+
+    ///Raised when a multi-byte code does not map to any valid code
+    InvalidCode {
+        ///Byte offset of the code in the compressed slice
+        offset: usize,
+    },
+
+    ///Raised when the length prefix of a framed record is too large to be valid
+    InvalidRecordLength {
+        ///Byte offset of the length prefix in the buffer
+        offset: usize,
+    },
+
+    ///Raised when the header was written by an incompatible version of the compressed format
+    UnsupportedVersion {
+        ///The version found in the header
+        version: u8,
+    },
+
+    ///Raised when the header fingerprint does not match the engine's fingerprint.
+    ///
+    /// This means the bytes were compressed using different tables, custom list or options, so decompressing them would give the wrong string.
+    TableMismatch {
+        ///Fingerprint of the engine used to decompress
+        expected: u32,
+        ///Fingerprint found in the header
+        found: u32,
+    },
+
+    ///Raised when an entry in a list passed to [Tables](crate::tables::Tables) is empty, or is not valid utf-8
+    InvalidTableEntry {
+        ///Line of the entry in its list, starting at 1
+        line: usize,
+    },
+
+    ///Raised when a list passed to [Tables](crate::tables::Tables) contains the same entry twice
+    DuplicateTableEntry {
+        ///Line of the second copy of the entry in its list, starting at 1
+        line: usize,
+    },
+
+    ///Raised when a list passed to [Tables](crate::tables::Tables) has too many entries (or, for the one byte wonder list, not exactly the right number)
+    InvalidTableLength {
+        ///Maximum number of entries in the list
+        max: usize,
+        ///Number of entries found
+        found: usize,
+    },
+
+    ///Raised when the one byte wonder list passed to [Tables](crate::tables::Tables) does not keep an ascii character at its own position
+    MissingAsciiEntry {
+        ///Position of the ascii character
+        index: usize,
+    },
+
+    ///Raised when an entry in the three byte uncommon list passed to [Tables](crate::tables::Tables) is also in the two byte common list, so it would never be used
+    UnusedTableEntry {
+        ///Line of the entry in the three byte uncommon list, starting at 1
+        line: usize,
+    },
+
+    ///Raised when an offset passed to [Engine::decompress_batch](crate::engine::Engine::decompress_batch) is smaller than the one before it, or past the end of the bytes
+    InvalidBatchOffset {
+        ///Position of the offset in the offsets slice
+        index: usize,
+    },
+
+    ///Raised when the reader passed to [Engine::decompress_from](crate::engine::Engine::decompress_from) returns an error
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
-impl From<bincode::Error> for Error {
-    fn from(value: bincode::Error) -> Self {
-        match value.deref() {
-            ErrorKind::InvalidCharEncoding => {
-                Error::InvalidUnicodeChar
-            }
-            ErrorKind::SizeLimit => {
-                Error::UnexpectedEndOfBytes
-            }
-            ErrorKind::Io(e) => {
-                match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => {
-                        Error::UnexpectedEndOfBytes
-                    }
-                    _ => {
-                        Error::OtherBincode(value)
-                    }
-                }
-            }
-            _ =>  {
-                Error::OtherBincode(value)
-            }
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Format(_) => write!(f, "Error converting IR to string"),
+            Error::UnexpectedEndOfBytes => write!(f, "Unexpected end of bytes. Deserialiser expected more bytes in the decompress slice"),
+            Error::InvalidUnicodeChar => write!(f, "Could not deserialize invalid unicode scalar value"),
+            Error::InvalidUtf8 { offset } => write!(f, "Code at byte {} is not valid utf-8, use decompress_bytes to decompress it", offset),
+            Error::InvalidCustomIndex { index, offset } => write!(f, "Custom index {} at byte {} is not in the custom list", index, offset),
+            Error::InvalidRepetitionIndex { index, offset } => write!(f, "Repetition index {} at byte {} is not in the repetitions list", index, offset),
+            Error::InvalidCode { offset } => write!(f, "Invalid code at byte {}", offset),
+            Error::InvalidRecordLength { offset } => write!(f, "Invalid record length at byte {}", offset),
+            Error::UnsupportedVersion { version } => write!(f, "Unsupported format version {}", version),
+            Error::TableMismatch { expected, found } => write!(f, "Table fingerprint {:#010x} does not match the engine's fingerprint {:#010x}", found, expected),
+            Error::InvalidTableEntry { line } => write!(f, "Table entry on line {} is empty or not valid utf-8", line),
+            Error::DuplicateTableEntry { line } => write!(f, "Table entry on line {} is a duplicate", line),
+            Error::InvalidTableLength { max, found } => write!(f, "Table has {} entries, but must have at most {} (exactly for the one byte wonder list)", found, max),
+            Error::MissingAsciiEntry { index } => write!(f, "One byte wonder entry {} must be the ascii character {:?}", index, *index as u8 as char),
+            Error::UnusedTableEntry { line } => write!(f, "Three byte entry on line {} is also a two byte entry, so it would never be used", line),
+            Error::InvalidBatchOffset { index } => write!(f, "Batch offset {} is smaller than the previous offset or past the end of the bytes", index),
+            #[cfg(feature = "std")]
+            Error::Io(_) => write!(f, "Error reading compressed bytes"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Format(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<core::fmt::Error> for Error {
+    fn from(value: core::fmt::Error) -> Self {
+        Error::Format(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::Io(value)
+    }
+}
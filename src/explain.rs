@@ -0,0 +1,129 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::engine::Engine;
+use crate::ir::{Case, CodeType};
+
+///The type of code used to encode part of a string
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    ///A single byte from the one byte wonder list (ascii characters and common sequences)
+    OneByteWonder,
+
+    ///Two bytes representing a word from the two byte common list
+    TwoByteCommon,
+
+    ///Three bytes representing a word from the three byte uncommon list
+    ThreeByteUncommon,
+
+    ///A number stored in binary, along with any sign, leading zeros, decimal mark and grouping separators
+    Number,
+
+    ///A run of hex digits or a UUID, stored as two digits per byte
+    Hex,
+
+    ///An ISO 8601 date or date and time, stored as its fields packed together
+    DateTime,
+
+    ///An IPv4 or IPv6 address (with any port), stored as the bytes of the address
+    IpAddress,
+
+    ///A sequence from the repetitions list, repeated several times
+    Repetition,
+
+    ///A string from the engine's custom list
+    Custom,
+
+    ///A non-printable ascii control character
+    Control,
+
+    ///A unicode character stored as is
+    Unicode,
+
+    ///A byte that isn't valid utf-8, only found in bytes compressed with [Engine::compress_bytes]
+    RawByte,
+
+    ///A run of characters from a single 128 codepoint unicode window, stored as one byte each
+    UnicodeWindow,
+
+    ///A two byte, three byte or custom entry with its first letter capitalised. Uses two more bytes than the entry on its own
+    Capitalised,
+
+    ///A two byte, three byte or custom entry in uppercase. Uses two more bytes than the entry on its own
+    Uppercase,
+}
+
+///Describes a single code in the compressed bytes, and the part of the string it encodes
+///
+/// Created with [Engine::explain]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    ///The byte range of the original string encoded by this token
+    pub span: Range<usize>,
+
+    ///The type of code
+    pub kind: TokenKind,
+
+    ///The list entry used by the code (not including any space prefix), or `None` for numbers and unicode characters
+    pub entry: Option<String>,
+
+    ///Index of the entry in its list (or the unicode window), or `None` for numbers and unicode characters
+    pub index: Option<usize>,
+
+    ///True if the code also encodes a space before the entry
+    pub space: bool,
+
+    ///The compressed bytes of this code
+    pub bytes: Vec<u8>,
+}
+
+impl Token {
+    pub (crate) fn new(code: & CodeType, start: usize, engine: & Engine) -> Self {
+
+        let (kind, entry, index, space) = Self::describe(code, engine);
+
+        //The span is the length of the decoded code, which is always the same as the length of the string it was created from
+        let mut decoded = String::new();
+        code.add_to_string(& mut decoded, engine, 0).expect("Codes created by the compressor are always valid");
+
+        let bytes = code.to_bytes(engine).parts().concat();
+
+        Self {
+            span: start..start + decoded.len(),
+            kind,
+            entry,
+            index,
+            space,
+            bytes,
+        }
+    }
+
+    fn describe(code: & CodeType, engine: & Engine) -> (TokenKind, Option<String>, Option<usize>, bool) {
+        match code {
+            CodeType::OneByteWonder(index) => (TokenKind::OneByteWonder, engine.tables.one_byte.entry(*index).map(String::from), Some(*index), false),
+            CodeType::TwoByteCommon(space, index) => (TokenKind::TwoByteCommon, engine.tables.two_byte.entry(*index).map(String::from), Some(*index), *space),
+            CodeType::ThreeByteUncommon(space, index) => (TokenKind::ThreeByteUncommon, engine.tables.three_byte.entry(*index).map(String::from), Some(*index), *space),
+            CodeType::UnicodeChar(_) => (TokenKind::Unicode, None, None, false),
+            CodeType::RawByte(_) => (TokenKind::RawByte, None, None, false),
+            CodeType::Number(_) | CodeType::FormattedNumber(_, _) => (TokenKind::Number, None, None, false),
+            CodeType::Hex(_, _, _) | CodeType::Uuid(_, _) => (TokenKind::Hex, None, None, false),
+            CodeType::DateTime(_) => (TokenKind::DateTime, None, None, false),
+            CodeType::Ipv4(_, _) | CodeType::Ipv6(_, _) => (TokenKind::IpAddress, None, None, false),
+            CodeType::Unprintable(index) => (TokenKind::Control, Some(crate::map::Controls::get_index(*index).to_string()), Some(*index), false),
+            CodeType::Repetitions(_, index) => (TokenKind::Repetition, Some(crate::map::Repetitions::get_index(*index).to_string()), Some(*index), false),
+            CodeType::Custom(space, index) => (TokenKind::Custom, engine.custom_map.get_by_right(index).map(|custom| String::from_utf8_lossy(custom).into_owned()), Some(*index), *space),
+            CodeType::UnicodeWindow(window, _) => (TokenKind::UnicodeWindow, None, Some(*window as usize), false),
+            CodeType::Cased(case, code) => {
+                let (_, entry, index, space) = Self::describe(code, engine);
+
+                let kind = match case {
+                    Case::Capitalise => TokenKind::Capitalised,
+                    Case::Uppercase => TokenKind::Uppercase,
+                };
+
+                (kind, entry, index, space)
+            }
+        }
+    }
+}
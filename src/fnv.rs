@@ -0,0 +1,9 @@
+//FNV-1a, used to fingerprint the tables. This file is included by both build.rs and src/header.rs, so the fingerprint the build script
+//generates for the built in lists always matches the one computed for lists loaded at runtime
+pub (crate) fn fnv1a(mut hash: u32, bytes: & [u8]) -> u32 {
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
@@ -0,0 +1,117 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::cursor::Cursor;
+use crate::engine::Engine;
+use crate::error::{Error, Result};
+
+//Each byte of the length prefix holds 7 bits of the length, with the top bit set if more bytes follow. usize is at most 64 bits, which is 10 bytes
+const MAX_LENGTH_BYTES: usize = 10;
+
+//Encodes the record length as an unsigned LEB128 variable length integer
+pub (crate) fn encode_length(mut length: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAX_LENGTH_BYTES);
+
+    loop {
+        let byte = (length & 0x7f) as u8;
+        length >>= 7;
+
+        if length == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+//Decodes a length prefix from the start of the slice, returning the length and the number of bytes the prefix took up
+pub (crate) fn decode_length(bytes: & [u8], offset: usize) -> Result<(usize, usize)> {
+    let mut length = 0usize;
+
+    for (i, byte) in bytes.iter().enumerate().take(MAX_LENGTH_BYTES) {
+        let bits = (*byte & 0x7f) as usize;
+
+        //Make sure the length still fits in a usize
+        if i * 7 >= usize::BITS as usize || (bits << (i * 7)) >> (i * 7) != bits {
+            return Err(Error::InvalidRecordLength { offset });
+        }
+
+        length |= bits << (i * 7);
+
+        if byte & 0x80 == 0 {
+            return Ok((length, i + 1));
+        }
+    }
+
+    if bytes.len() < MAX_LENGTH_BYTES {
+        Err(Error::UnexpectedEndOfBytes)
+    } else {
+        Err(Error::InvalidRecordLength { offset })
+    }
+}
+
+///Iterator over the strings stored in a buffer of concatenated records
+///
+/// Records are created with [Engine::compress_framed] or [Engine::compress_framed_to_vec] and can be read with [Engine::framed_reader].
+/// Each item is the decompressed string or the error encountered while decompressing it.
+/// Once an error is returned the reader cannot find the start of the next record, so the iterator ends.
+pub struct FramedReader<'a> {
+    bytes: & 'a [u8],
+    engine: & 'a Engine,
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> FramedReader<'a> {
+
+    ///Creates a reader over the buffer, using `engine` to decompress each record.
+    ///
+    /// The engine must have the same options as the engine used to compress the records.
+    pub fn new(bytes: & 'a [u8], engine: & 'a Engine) -> Self {
+        Self {
+            bytes,
+            engine,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    ///Returns the bytes that have not been read yet
+    pub fn remaining(&self) -> & 'a [u8] {
+        &self.bytes[self.offset..]
+    }
+
+    fn read_record(& mut self) -> Result<String> {
+        let (length, prefix) = decode_length(self.remaining(), self.offset)?;
+
+        let start = self.offset + prefix;
+
+        if self.bytes.len() - start < length {
+            return Err(Error::UnexpectedEndOfBytes);
+        }
+
+        let mut string = String::new();
+
+        self.engine.decode(& mut Cursor::new(&self.bytes[start..start + length], start), & mut string)?;
+
+        self.offset = start + length;
+
+        Ok(string)
+    }
+}
+
+impl Iterator for FramedReader<'_> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset == self.bytes.len() {
+            return None;
+        }
+
+        let record = self.read_record();
+
+        self.failed = record.is_err();
+
+        Some(record)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::cursor::Source;
+use crate::engine::Engine;
+use crate::error::{Error, Result};
+
+//Version of the compressed format, stored in the header. This must be incremented whenever the meaning of the codes changes
+pub (crate) const FORMAT_VERSION: u8 = 7;
+
+//Oldest version that can still be read. Version 2 added case modifiers and unicode windows, version 3 raw bytes, version 4 formatted numbers, version 5 hex codes, version 6 dates and version 7 IP addresses, which all use codes older versions never wrote, so older bytes decode the same
+const MIN_FORMAT_VERSION: u8 = 1;
+
+//One byte for the version, followed by the four byte fingerprint
+pub (crate) const HEADER_LENGTH: usize = 5;
+
+include!("fnv.rs");
+
+//Combines the fingerprint of the tables with the custom list and options of an engine
+pub (crate) fn engine_fingerprint<'a, I: Iterator<Item = & 'a [u8]>>(tables: u32, custom: I, custom_spaces: bool) -> u32 {
+    let mut fingerprint = tables;
+
+    for string in custom {
+        fingerprint = fnv1a(fingerprint, string);
+        fingerprint = fnv1a(fingerprint, &[0xff]);
+    }
+
+    fnv1a(fingerprint, &[custom_spaces as u8])
+}
+
+pub (crate) fn header_bytes(engine: & Engine) -> [u8; HEADER_LENGTH] {
+    let mut header = [0u8; HEADER_LENGTH];
+
+    header[0] = FORMAT_VERSION;
+    header[1..].copy_from_slice(&engine.fingerprint.to_le_bytes());
+
+    header
+}
+
+pub (crate) fn read_header<S: Source>(source: & mut S, engine: & Engine) -> Result<()> {
+    let mut header = [0u8; HEADER_LENGTH];
+
+    for byte in header.iter_mut() {
+        *byte = source.read_byte()?;
+    }
+
+    if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&header[0]) {
+        return Err(Error::UnsupportedVersion { version: header[0] });
+    }
+
+    let found = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+
+    if found != engine.fingerprint {
+        return Err(Error::TableMismatch { expected: engine.fingerprint, found });
+    }
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
-use std::str::from_utf8_unchecked;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::str::from_utf8_unchecked;
 use crate::engine::Engine;
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 #[derive(PartialEq)]
 pub (crate) enum CodeType {
@@ -25,9 +27,44 @@ pub (crate) enum CodeType {
     /// Takes up one more byte than the scalar value itself.
     UnicodeChar(char),
 
+    ///Represents a non-ascii byte that isn't part of a valid utf-8 sequence, which only appears in bytes compressed with [Engine::compress_bytes].
+    ///
+    /// Takes up two bytes for 0x80 to 0xBF, and three bytes for 0xC0 to 0xFF.
+    RawByte(u8),
+
     ///Represents all numbers larger than 9999 (as between 1 and 8 bytes, inclusive, for numbers up to 2^66)
     Number(u128),
 
+    ///Represents a number with a sign, leading zeros, a decimal point or grouping separators (see [NumberFormat]), such as `-19.99`, `000451` or `1,250,000`.
+    ///
+    /// Takes up three bytes for the code and format, one more if there are leading zeros, then the digits (without the decimal point) as a varint.
+    FormattedNumber(NumberFormat, u64),
+
+    ///Represents a run of hex digits whose letters are all lowercase (false) or all uppercase (true), such as a hash or a colour.
+    ///
+    /// Holds the number of digits and the digits packed two to a byte, high half first. Takes up three bytes plus one byte per two digits.
+    Hex(bool, u8, Vec<u8>),
+
+    ///Represents a UUID in the canonical 8-4-4-4-12 form, with lowercase (false) or uppercase (true) letters.
+    ///
+    /// Takes up two bytes plus the 16 bytes of the UUID.
+    Uuid(bool, [u8; 16]),
+
+    ///Represents an ISO 8601 date, optionally followed by a time (see [DateTime]), such as `2026-10-18` or `2026-10-18T08:38:00Z`.
+    ///
+    /// Takes up three bytes plus the fields packed together, which is six bytes for a date and eight for a date and time to the second.
+    DateTime(DateTime),
+
+    ///Represents an IPv4 address in dotted decimal, with an optional port, such as `192.168.100.254` or `10.0.0.1:8080`.
+    ///
+    /// Takes up two bytes plus the four bytes of the address, and two more for a port.
+    Ipv4([u8; 4], Option<u16>),
+
+    ///Represents an IPv6 address written as described by its [Ipv6Format], such as `2001:db8::8a2e:370:7334` or `[::1]:8080`.
+    ///
+    /// Takes up three bytes plus the 16 bytes of the address, and two more for a port.
+    Ipv6([u8; 16], Ipv6Format),
+
     ///Represents the unprintable ascii control bytes.
     ///
     /// Represented as 2 bytes
@@ -36,26 +73,399 @@ pub (crate) enum CodeType {
     Repetitions(u32, usize),
 
     Custom(bool, usize),
+
+    ///Applies a case modifier to the two byte, three byte or custom code that follows it.
+    ///
+    /// Takes up two more bytes than the code itself.
+    Cased(Case, Box<CodeType>),
+
+    ///Represents a run of characters from a single 128 codepoint unicode window, mixed with ascii characters (see [Window]).
+    ///
+    /// Takes up four bytes plus one byte per character.
+    UnicodeWindow(u8, Vec<u8>),
+}
+
+///Unicode windows split the first 32768 codepoints (U+0000 to U+7FFF) into 256 windows of 128 codepoints.
+///
+/// Most alphabets (Latin-1, Greek, Cyrillic, Hebrew, Arabic, ...) fit in one or two windows, so in a window run
+/// each character takes one byte: characters in the window are stored as their position in the window, and ascii characters as themselves plus 128.
+pub (crate) struct Window;
+
+impl Window {
+
+    const SIZE: u32 = 128;
+
+    //The window containing the character, if it is a non-ascii character that can be stored in a window
+    pub (crate) fn of(ch: char) -> Option<u8> {
+        let window = ch as u32 / Self::SIZE;
+
+        if ch.is_ascii() || window > u8::MAX as u32 {
+            None
+        } else {
+            Some(window as u8)
+        }
+    }
+
+    //The byte storing the character in a run using the window, if it can be stored
+    pub (crate) fn byte(window: u8, ch: char) -> Option<u8> {
+        if ch.is_ascii() {
+            Some(ch as u8 + Self::SIZE as u8)
+        } else if Self::of(ch) == Some(window) {
+            Some((ch as u32 % Self::SIZE) as u8)
+        } else {
+            None
+        }
+    }
+
+    //The character stored by the byte in a run using the window
+    pub (crate) fn char(window: u8, byte: u8) -> char {
+        if byte as u32 >= Self::SIZE {
+            (byte - Self::SIZE as u8) as char
+        } else {
+            //Windows never reach the surrogate range, so every position is a valid character
+            char::from_u32(window as u32 * Self::SIZE + byte as u32).unwrap()
+        }
+    }
+}
+
+///How the digits of a [CodeType::FormattedNumber] are written out
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) struct NumberFormat {
+    ///Starts with a minus sign
+    pub (crate) negative: bool,
+
+    ///Index into [NumberFormat::STYLES]
+    pub (crate) style: u8,
+
+    ///Number of digits after the decimal mark
+    pub (crate) decimals: u8,
+
+    ///Number of zeros before the first digit the value needs
+    pub (crate) zeros: u8,
+}
+
+impl NumberFormat {
+
+    ///The grouping separator (between every three digits before the decimal mark) and decimal mark of each style
+    pub (crate) const STYLES: [(Option<char>, char); 4] = [(None, '.'), (Some(','), '.'), (Some('.'), ','), (None, ',')];
+
+    pub (crate) const MAX_DECIMALS: u8 = 7;
+
+    //Writes the value in this format. The value holds every digit, so `decimals` of them go after the decimal mark
+    pub (crate) fn write<W: core::fmt::Write>(&self, value: u64, string: & mut W) -> core::fmt::Result {
+        let (grouping, mark) = Self::STYLES[self.style as usize];
+        let decimals = self.decimals as usize;
+
+        //u64::MAX has 20 digits, which is more than MAX_DECIMALS + 1
+        let mut digits = [b'0'; 20];
+        let mut length = 0;
+        let mut rest = value;
+
+        loop {
+            digits[digits.len() - 1 - length] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+            length += 1;
+
+            if rest == 0 {
+                break;
+            }
+        }
+
+        //There is always at least one digit before the decimal mark, as in 0.05
+        let length = length.max(decimals + 1);
+        let (integer, fraction) = digits[digits.len() - length..].split_at(length - decimals);
+
+        if self.negative {
+            string.write_char('-')?;
+        }
+
+        for _ in 0..self.zeros {
+            string.write_char('0')?;
+        }
+
+        for (i, digit) in integer.iter().enumerate() {
+            if let Some(separator) = grouping.filter(|_| i > 0 && (integer.len() - i) % 3 == 0) {
+                string.write_char(separator)?;
+            }
+
+            string.write_char(*digit as char)?;
+        }
+
+        if decimals > 0 {
+            string.write_char(mark)?;
+
+            for digit in fraction {
+                string.write_char(*digit as char)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//Writes `digits` hex digits from the bytes, two to a byte with the high half first, with a dash before each digit in `dashes`
+fn write_hex<W: core::fmt::Write>(string: & mut W, uppercase: bool, digits: usize, bytes: & [u8], dashes: & [usize]) -> core::fmt::Result {
+    for i in 0..digits {
+        if dashes.contains(&i) {
+            string.write_char('-')?;
+        }
+
+        let half = if i % 2 == 0 { bytes[i / 2] >> 4 } else { bytes[i / 2] & 0x0f };
+
+        //to_digit gives lowercase letters
+        let digit = char::from_digit(half as u32, 16).unwrap();
+
+        string.write_char(if uppercase { digit.to_ascii_uppercase() } else { digit })?;
+    }
+
+    Ok(())
+}
+
+//Positions of the dashes in a UUID, counted in digits
+const UUID_DASHES: [usize; 4] = [8, 12, 16, 20];
+
+///An ISO 8601 date in the `YYYY-MM-DD` form, with an optional time
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) struct DateTime {
+    pub (crate) year: u16,
+
+    ///From 1 to 12
+    pub (crate) month: u8,
+
+    ///From 1 to 31. Days aren't checked against the month, as only the text has to be reproduced
+    pub (crate) day: u8,
+
+    pub (crate) time: Option<Time>,
+}
+
+///A time following a [DateTime], in the `HH:MM`, `HH:MM:SS` or `HH:MM:SS.fff` form, with an optional time zone
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) struct Time {
+    ///`T` or a space
+    pub (crate) separator: char,
+
+    pub (crate) hour: u8,
+
+    pub (crate) minute: u8,
+
+    ///From 0 to 60 (for leap seconds), or `None` for times without seconds
+    pub (crate) second: Option<u8>,
+
+    ///Number of digits after the seconds, up to [Time::MAX_FRACTION_DIGITS]
+    pub (crate) fraction_digits: u8,
+
+    pub (crate) fraction: u32,
+
+    pub (crate) zone: Zone,
+}
+
+impl Time {
+    pub (crate) const MAX_FRACTION_DIGITS: u8 = 9;
+}
+
+///The time zone at the end of a [Time]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) enum Zone {
+    ///No time zone
+    Local,
+
+    ///`Z`
+    Utc,
+
+    ///An offset from UTC such as `+05:30` (or `+0530` without the colon). Minutes are from 0 to 1439, and `-00:00` is kept apart from `+00:00`
+    Offset { negative: bool, minutes: u16, colon: bool },
+}
+
+impl DateTime {
+
+    //Writes the date and time in the same form they were read from
+    pub (crate) fn write<W: core::fmt::Write>(&self, string: & mut W) -> core::fmt::Result {
+        write!(string, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+
+        let time = match &self.time {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+
+        write!(string, "{}{:02}:{:02}", time.separator, time.hour, time.minute)?;
+
+        if let Some(second) = time.second {
+            write!(string, ":{:02}", second)?;
+        }
+
+        if time.fraction_digits > 0 {
+            write!(string, ".{:0width$}", time.fraction, width = time.fraction_digits as usize)?;
+        }
+
+        match time.zone {
+            Zone::Local => Ok(()),
+            Zone::Utc => string.write_char('Z'),
+            Zone::Offset { negative, minutes, colon } => {
+                write!(string, "{}{:02}{}{:02}", if negative { '-' } else { '+' }, minutes / 60, if colon { ":" } else { "" }, minutes % 60)
+            }
+        }
+    }
+}
+
+///How the text of a [CodeType::Ipv6] address is written
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) struct Ipv6Format {
+    ///Letters are uppercase
+    pub (crate) uppercase: bool,
+
+    ///Every group is written with four digits and no groups are left out, as in `2001:0db8:0000:0000:0000:0000:0000:0001`.
+    /// Otherwise the address is in the canonical form from RFC 5952, as in `2001:db8::1`
+    pub (crate) expanded: bool,
+
+    ///The port, which puts the address in brackets as in `[::1]:8080`
+    pub (crate) port: Option<u16>,
+}
+
+impl Ipv6Format {
+
+    pub (crate) fn write<W: core::fmt::Write>(&self, octets: [u8; 16], string: & mut W) -> core::fmt::Result {
+        use core::fmt::Write;
+
+        let address = core::net::Ipv6Addr::from(octets);
+
+        if self.port.is_some() {
+            string.write_char('[')?;
+        }
+
+        let mut string = Letters { inner: string, uppercase: self.uppercase };
+
+        if self.expanded {
+            for (i, group) in address.segments().iter().enumerate() {
+                write!(string, "{}{:04x}", if i > 0 { ":" } else { "" }, group)?;
+            }
+        } else {
+            write!(string, "{}", address)?;
+        }
+
+        if let Some(port) = self.port {
+            write!(string, "]:{}", port)?;
+        }
+
+        Ok(())
+    }
+}
+
+//Passes text on, uppercasing any letters if `uppercase` is set
+struct Letters<'a, W> {
+    inner: & 'a mut W,
+    uppercase: bool,
+}
+
+impl<W: core::fmt::Write> core::fmt::Write for Letters<'_, W> {
+    fn write_str(& mut self, s: & str) -> core::fmt::Result {
+        if !self.uppercase {
+            return self.inner.write_str(s);
+        }
+
+        for ch in s.chars() {
+            self.inner.write_char(ch.to_ascii_uppercase())?;
+        }
+
+        Ok(())
+    }
+}
+
+///Case modifiers, which let capitalised and uppercase words use the lowercase entries in the lists
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub (crate) enum Case {
+    ///Uppercase the first letter of the entry
+    Capitalise,
+
+    ///Uppercase every letter of the entry
+    Uppercase,
+}
+
+impl Case {
+
+    //Finds the modifier that turns the lowercase version of `word` back into `word`, if there is one
+    pub (crate) fn detect(word: & [u8]) -> Option<Case> {
+        let (first, rest) = word.split_first()?;
+
+        if !first.is_ascii_uppercase() {
+            None
+        } else if !rest.iter().any(u8::is_ascii_uppercase) {
+            Some(Case::Capitalise)
+        } else if !rest.iter().any(u8::is_ascii_lowercase) {
+            Some(Case::Uppercase)
+        } else {
+            None
+        }
+    }
+
+    //Writes the entry with the modifier applied. Only ascii letters are changed, so the length stays the same
+    fn write<W: core::fmt::Write>(&self, string: & mut W, entry: & str) -> core::fmt::Result {
+        for (i, ch) in entry.chars().enumerate() {
+            let upper = match self {
+                Case::Capitalise => i == 0,
+                Case::Uppercase => true,
+            };
+
+            string.write_char(if upper { ch.to_ascii_uppercase() } else { ch })?;
+        }
+
+        Ok(())
+    }
 }
 
-impl std::fmt::Debug for CodeType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for CodeType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
+            //The tables are part of the engine, so codes from the tables are shown by index (use Engine::explain to see the entries)
             CodeType::OneByteWonder(i) => {
-                write!(f, "OneByteWonder({:?})", crate::map::OneByteMap::get_index(*i))
+                write!(f, "OneByteWonder({})", *i)
             }
             CodeType::TwoByteCommon(space, i) => {
-                write!(f, "TwoByteCommon(\"{}{}\")", if *space { " " } else { "" }, crate::map::TwoByteMap::get_index(*i))
+                write!(f, "TwoByteCommon({}{})", if *space { " " } else { "" }, *i)
             }
             CodeType::ThreeByteUncommon(space, i) => {
-                write!(f, "ThreeByteUncommon(\"{}{}\")", if *space { " " } else { "" }, crate::map::ThreeByteMap::get_index(*i))
+                write!(f, "ThreeByteUncommon({}{})", if *space { " " } else { "" }, *i)
             }
             CodeType::Number(num) => {
                 write!(f, "Number({})", *num)
             }
+            CodeType::FormattedNumber(format, value) => {
+                write!(f, "FormattedNumber(\"")?;
+                format.write(*value, f)?;
+                write!(f, "\")")
+            }
+            CodeType::Hex(uppercase, digits, bytes) => {
+                write!(f, "Hex(\"")?;
+                write_hex(f, *uppercase, *digits as usize, bytes, &[])?;
+                write!(f, "\")")
+            }
+            CodeType::Uuid(uppercase, bytes) => {
+                write!(f, "Uuid(\"")?;
+                write_hex(f, *uppercase, 32, bytes, &UUID_DASHES)?;
+                write!(f, "\")")
+            }
+            CodeType::DateTime(date_time) => {
+                write!(f, "DateTime(\"")?;
+                date_time.write(f)?;
+                write!(f, "\")")
+            }
+            CodeType::Ipv4(octets, port) => {
+                write!(f, "Ipv4(\"{}", core::net::Ipv4Addr::from(*octets))?;
+                if let Some(port) = port {
+                    write!(f, ":{}", port)?;
+                }
+                write!(f, "\")")
+            }
+            CodeType::Ipv6(octets, format) => {
+                write!(f, "Ipv6(\"")?;
+                format.write(*octets, f)?;
+                write!(f, "\")")
+            }
             CodeType::UnicodeChar(ch) => {
                 write!(f, "UnicodeChar({:?})", *ch)
             }
+            CodeType::RawByte(byte) => {
+                write!(f, "RawByte({:#04x})", *byte)
+            }
             CodeType::Unprintable(i) => {
                 write!(f, "Unprintable({:?})", crate::map::Controls::get_index(*i))
             }
@@ -69,44 +479,112 @@ impl std::fmt::Debug for CodeType {
             CodeType::Custom(space, ind) => {
                 write!(f, "Custom({}{})", if *space { " " } else { "" }, *ind)
             }
+            CodeType::Cased(case, code) => {
+                write!(f, "{:?}({:?})", case, code)
+            }
+            CodeType::UnicodeWindow(window, run) => {
+                write!(f, "UnicodeWindow(U+{:04X}, \"", *window as u32 * Window::SIZE)?;
+                for byte in run {
+                    write!(f, "{}", Window::char(*window, *byte))?;
+                }
+                write!(f, "\")")
+            }
         }
     }
 }
 
 impl CodeType {
-    pub fn add_to_string(&self, string: & mut String, engine: & Engine) -> Result<()> {
-        use std::fmt::Write;
+    ///Appends the decoded code to the string (or any other [core::fmt::Write]).
+    ///
+    /// `offset` is the position of the code in the compressed bytes and is only used for error reporting
+    pub fn add_to_string<W: core::fmt::Write>(&self, string: & mut W, engine: & Engine, offset: usize) -> Result<()> {
 
         match self {
             CodeType::OneByteWonder(index) => {
-                write!(string, "{}", crate::map::OneByteMap::get_index(*index))?;
+                write!(string, "{}", engine.tables.one_byte.entry(*index).ok_or(Error::InvalidCode { offset })?)?;
             }
-            CodeType::TwoByteCommon(space, index) => {
+            CodeType::TwoByteCommon(_, _) | CodeType::ThreeByteUncommon(_, _) => {
+                let (space, entry) = self.word(engine, offset)?;
 
-                write!(string, "{}{}", if *space { " " } else { "" }, crate::map::TwoByteMap::get_index(*index) )?;
-            }
-            CodeType::ThreeByteUncommon(space, index) => {
-                write!(string, "{}{}", if *space { " " } else { "" }, crate::map::ThreeByteMap::get_index(*index))?;
+                write!(string, "{}{}", if space { " " } else { "" }, entry)?;
             }
             CodeType::UnicodeChar(ch) => {
                 write!(string, "{}", *ch)?;
             }
+            CodeType::RawByte(_) => {
+                //Only Engine::decompress_bytes can output bytes that aren't valid utf-8
+                return Err(Error::InvalidUtf8 { offset });
+            }
             CodeType::Number(num) => {
                 write!(string, "{}", *num)?;
             }
+            CodeType::FormattedNumber(format, value) => {
+                format.write(*value, string)?;
+            }
+            CodeType::Hex(uppercase, digits, bytes) => {
+                write_hex(string, *uppercase, *digits as usize, bytes, &[])?;
+            }
+            CodeType::Uuid(uppercase, bytes) => {
+                write_hex(string, *uppercase, 32, bytes, &UUID_DASHES)?;
+            }
+            CodeType::DateTime(date_time) => {
+                date_time.write(string)?;
+            }
+            CodeType::Ipv4(octets, port) => {
+                write!(string, "{}", core::net::Ipv4Addr::from(*octets))?;
+
+                if let Some(port) = port {
+                    write!(string, ":{}", port)?;
+                }
+            }
+            CodeType::Ipv6(octets, format) => {
+                format.write(*octets, string)?;
+            }
             CodeType::Unprintable(index) => {
                 write!(string, "{}", crate::map::Controls::get_index(*index))?;
             }
             CodeType::Repetitions(count, index) => {
+                let sequence = crate::map::Repetitions::get(*index).ok_or(Error::InvalidRepetitionIndex { index: *index, offset })?;
+
                 for _ in 0..*count {
-                    write!(string, "{}", crate::map::Repetitions::get_index(*index))?;
+                    write!(string, "{}", sequence)?;
                 }
             }
             CodeType::Custom(space, index) => {
-                write!(string, "{}{}", if *space { " " } else { "" }, unsafe { from_utf8_unchecked( engine.custom_map.get_by_right(index).unwrap() )} )?; //This unsafe is justified as the user can only input valid utf-8 strings into the custom map
+                let custom = engine.custom_map.get_by_right(index).ok_or(Error::InvalidCustomIndex { index: *index, offset })?;
+
+                write!(string, "{}{}", if *space { " " } else { "" }, unsafe { from_utf8_unchecked(custom) } )?; //This unsafe is justified as the user can only input valid utf-8 strings into the custom map
+            }
+            CodeType::Cased(case, code) => {
+                let (space, entry) = code.word(engine, offset)?;
+
+                if space {
+                    string.write_char(' ')?;
+                }
+
+                case.write(string, entry)?;
+            }
+            CodeType::UnicodeWindow(window, run) => {
+                for byte in run {
+                    string.write_char(Window::char(*window, *byte))?;
+                }
             }
         }
 
         Ok(())
     }
+
+    //Gets the space prefix and entry of a code that can follow a case modifier
+    pub (crate) fn word<'a>(&self, engine: & 'a Engine, offset: usize) -> Result<(bool, & 'a str)> {
+        match self {
+            CodeType::TwoByteCommon(space, index) => Ok((*space, engine.tables.two_byte.entry(*index).ok_or(Error::InvalidCode { offset })?)),
+            CodeType::ThreeByteUncommon(space, index) => Ok((*space, engine.tables.three_byte.entry(*index).ok_or(Error::InvalidCode { offset })?)),
+            CodeType::Custom(space, index) => {
+                let custom = engine.custom_map.get_by_right(index).ok_or(Error::InvalidCustomIndex { index: *index, offset })?;
+
+                Ok((*space, unsafe { from_utf8_unchecked(custom) })) //See add_to_string
+            }
+            _ => Err(Error::InvalidCode { offset }),
+        }
+    }
 }
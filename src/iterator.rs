@@ -1,24 +1,53 @@
-use std::str::from_utf8_unchecked;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
+use core::str::from_utf8_unchecked;
 use crate::engine::Engine;
-use crate::ir::{CodeType};
+use crate::ir::{Case, CodeType, DateTime, Ipv6Format, NumberFormat, Time, Window, Zone};
 use crate::bi::{Match, Bi};
 
 pub (crate) struct CodeIterator<'a> {
     main: & 'a [u8],
     engine: & 'a Engine,
+    //True if main is known to be valid utf-8, starting on a char boundary
+    utf8: bool,
 }
 
+//Longest stretch of bytes checked for utf-8 at once when compressing bytes. Nothing (not even a unicode window run) needs to look further ahead
+const TEXT_LOOKAHEAD: usize = 1024;
+
 impl<'a> CodeIterator<'a> {
 
-    pub (crate) fn new(s: & 'a str, engine: & 'a Engine) -> Self {
+    //If `utf8` is false, bytes that aren't valid utf-8 are encoded as raw bytes
+    pub (crate) fn new(main: & 'a [u8], utf8: bool, engine: & 'a Engine) -> Self {
         Self {
-            main: s.as_bytes(),
+            main,
             engine,
+            utf8,
+        }
+    }
+
+    //An iterator over part of the same bytes
+    fn sub(&self, main: & 'a [u8]) -> Self {
+        Self::new(main, self.utf8, self.engine)
+    }
+
+    //The start of the bytes that is valid utf-8, which is all of them when compressing a string
+    fn text(&self) -> & 'a str {
+        if self.utf8 {
+            return unsafe { from_utf8_unchecked(self.main) }; //We can use unchecked here because we will always start at a unicode boundary
+        }
+
+        let ahead = &self.main[..self.main.len().min(TEXT_LOOKAHEAD)];
+
+        match core::str::from_utf8(ahead) {
+            Ok(text) => text,
+            Err(e) => unsafe { from_utf8_unchecked(&ahead[..e.valid_up_to()]) }, //valid_up_to is the length of the valid prefix, so this is valid utf-8
         }
     }
 
     fn is_digit(ch: u8) -> bool {
-        ch >= '0' as u8 && ch <= '9' as u8
+        ch.is_ascii_digit()
     }
 
     fn try_number(&self) -> Option<(u128, usize)> {
@@ -31,7 +60,7 @@ impl<'a> CodeIterator<'a> {
 
         let mut length = 0;
 
-        for (i, ch) in (&self.main[..]).iter().enumerate() {
+        for (i, ch) in self.main.iter().enumerate() {
             if Self::is_digit(*ch) /*|| i == self.main.len() - 1*/ {
                 length = i+1;
             }
@@ -46,10 +75,10 @@ impl<'a> CodeIterator<'a> {
         }
 
         //Attempt to convert this number into a u64.
-        let large: u128 = unsafe { std::str::from_utf8_unchecked(&self.main[0..length]) }.parse().ok()?; //The bytes in &self.main[0..length] are all ascii numbers, so this unchecked is ok
+        let large: u128 = unsafe { from_utf8_unchecked(&self.main[0..length]) }.parse().ok()?; //The bytes in &self.main[0..length] are all ascii numbers, so this unchecked is ok
 
-        //Make sure it fits in 42 bits
-        if large >= (2 << 66) {
+        //Make sure it fits in 66 bits (8 bytes plus the two bits in the code)
+        if large >= (1 << 66) {
             return None;
         }
 
@@ -61,7 +90,383 @@ impl<'a> CodeIterator<'a> {
         Some((large, length))
     }
 
-    fn try_repetitions(&self) -> Option<(usize, Match)> {
+    //Every number of 4 or more digits at the start of the string that fits in a number code, for the optimal parser.
+    //Numbers that fit have at most 20 digits, so only that many prefixes of the digit run are tried
+    fn number_prefixes(&self) -> Vec<(usize, CodeType)> {
+        (4..=self.digit_run(0, 20)).filter_map(|length| self.sub(&self.main[..length]).try_number()).map(|(number, length)| (length, CodeType::Number(number))).collect()
+    }
+
+    //Matches the longest number at the start of the string with a sign, leading zeros, a decimal mark or grouping separators, in any of the styles.
+    //Plain runs of digits are left to try_number, which stores them in fewer bytes
+    fn try_formatted_number(&self) -> Option<(usize, CodeType)> {
+        //Earlier styles win ties, as the matches are listed style by style
+        self.formatted_numbers().into_iter().rev().max_by_key(|(length, _)| *length)
+    }
+
+    //Every number at the start of the string with a sign, leading zeros, a decimal mark or grouping separators, in every style and ending after any of its digits
+    fn formatted_numbers(&self) -> Vec<(usize, CodeType)> {
+
+        let negative = self.main.first() == Some(&b'-');
+        let start = negative as usize;
+
+        if !self.main.get(start).is_some_and(|ch| Self::is_digit(*ch)) {
+            return Vec::new();
+        }
+
+        let digit_at = |i: usize| self.main.get(i).copied().filter(|ch| Self::is_digit(*ch));
+
+        let mut matches = Vec::new();
+
+        for (style, (grouping, mark)) in NumberFormat::STYLES.iter().enumerate() {
+            let mut value = 0u64;
+            let mut digits = 0;
+            let mut decimals = 0;
+            let mut fraction = false;
+            let mut i = start;
+
+            loop {
+                if let Some(digit) = digit_at(i) {
+                    value = match value.checked_mul(10).and_then(|v| v.checked_add((digit - b'0') as u64)) {
+                        Some(value) => value,
+                        None => break,
+                    };
+
+                    digits += 1;
+                    decimals += fraction as usize;
+                    i += 1;
+
+                    if decimals > NumberFormat::MAX_DECIMALS as usize {
+                        break;
+                    }
+
+                    //Digits the value needs, which is at least one more than the decimals, as in 0.05
+                    let needed = (value.checked_ilog10().unwrap_or(0) as usize + 1).max(decimals + 1);
+
+                    //Leading zeros past the most a code can hold stay too many however many digits follow them
+                    let zeros = match u8::try_from(digits - needed) {
+                        Ok(zeros) => zeros,
+                        Err(_) => break,
+                    };
+
+                    if !negative && zeros == 0 && i - start == digits {
+                        continue;
+                    }
+
+                    let format = NumberFormat { negative, style: style as u8, decimals: decimals as u8, zeros };
+
+                    //Separators in the wrong places (such as 12,34) aren't written back the same way, so make sure the number round trips
+                    let mut text = String::new();
+
+                    if format.write(value, & mut text).is_ok() && text.as_bytes() == &self.main[..i] {
+                        matches.push((i, CodeType::FormattedNumber(format, value)));
+                    }
+                } else if !fraction && grouping.is_some_and(|g| self.main.get(i) == Some(&(g as u8))) && digit_at(i + 1).is_some() {
+                    i += 1;
+                } else if !fraction && self.main.get(i) == Some(&(*mark as u8)) && digit_at(i + 1).is_some() {
+                    fraction = true;
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        matches
+    }
+
+    //The value of a hex digit, if the byte is one. Letters must match the case of any earlier letters, which is set by the first letter
+    fn hex_digit(ch: u8, uppercase: & mut Option<bool>) -> Option<u8> {
+        let value = (ch as char).to_digit(16)?;
+
+        if ch.is_ascii_alphabetic() && *uppercase.get_or_insert(ch.is_ascii_uppercase()) != ch.is_ascii_uppercase() {
+            return None;
+        }
+
+        Some(value as u8)
+    }
+
+    //The longest run of hex digits at the start of the string (up to 255, the most a code can hold) with every letter in the same case, packed two to a byte.
+    //Shorter runs never take fewer bytes than the plain codes
+    fn hex_run(&self) -> Option<(usize, CodeType)> {
+        self.hex_runs().pop()
+    }
+
+    //Every run of 4 or more hex digits at the start of the string, from shortest to longest, for the optimal parser
+    fn hex_runs(&self) -> Vec<(usize, CodeType)> {
+
+        let mut uppercase = None;
+        let mut bytes = Vec::new();
+        let mut runs = Vec::new();
+
+        for (i, ch) in self.main.iter().take(u8::MAX as usize).enumerate() {
+            let value = match Self::hex_digit(*ch, & mut uppercase) {
+                Some(value) => value,
+                None => break,
+            };
+
+            if i % 2 == 0 {
+                bytes.push(value << 4);
+            } else {
+                *bytes.last_mut().unwrap() |= value;
+            }
+
+            if i + 1 >= 4 {
+                runs.push((i + 1, CodeType::Hex(uppercase.unwrap_or(false), (i + 1) as u8, bytes.clone())));
+            }
+        }
+
+        runs
+    }
+
+    //Matches a UUID in the canonical 8-4-4-4-12 form at the start of the string, with every letter in the same case
+    fn try_uuid(&self) -> Option<(usize, CodeType)> {
+
+        const LENGTH: usize = 36;
+
+        let text = self.main.get(..LENGTH)?;
+
+        let mut uppercase = None;
+        let mut bytes = [0u8; 16];
+        let mut digits = 0;
+
+        for (i, ch) in text.iter().enumerate() {
+            if matches!(i, 8 | 13 | 18 | 23) {
+                if *ch != b'-' {
+                    return None;
+                }
+
+                continue;
+            }
+
+            let value = Self::hex_digit(*ch, & mut uppercase)?;
+
+            bytes[digits / 2] |= if digits % 2 == 0 { value << 4 } else { value };
+            digits += 1;
+        }
+
+        Some((LENGTH, CodeType::Uuid(uppercase.unwrap_or(false), bytes)))
+    }
+
+    //The value of the `count` digits at `start`, if they are all digits
+    fn digits(&self, start: usize, count: usize) -> Option<u32> {
+        let digits = self.main.get(start..start + count)?;
+
+        digits.iter().try_fold(0, |value, ch| Self::is_digit(*ch).then(|| value * 10 + (ch - b'0') as u32))
+    }
+
+    //Matches an ISO 8601 date at the start of the string, along with any time and time zone after it, as in 2026-10-18T08:38:00.123+05:30
+    fn try_date_time(&self) -> Option<(usize, CodeType)> {
+
+        let s = self.main;
+
+        let year = self.digits(0, 4)?;
+        let month = self.digits(5, 2).filter(|month| (1..=12).contains(month))?;
+        let day = self.digits(8, 2).filter(|day| (1..=31).contains(day))?;
+
+        if s[4] != b'-' || s[7] != b'-' {
+            return None;
+        }
+
+        let mut date_time = DateTime { year: year as u16, month: month as u8, day: day as u8, time: None };
+
+        let separator = match s.get(10) {
+            Some(b'T') => 'T',
+            Some(b' ') => ' ',
+            _ => return Some((10, CodeType::DateTime(date_time))),
+        };
+
+        let hour = self.digits(11, 2).filter(|hour| *hour < 24);
+        let minute = self.digits(14, 2).filter(|minute| *minute < 60);
+
+        let (hour, minute) = match (hour, minute, s.get(13)) {
+            (Some(hour), Some(minute), Some(b':')) => (hour as u8, minute as u8),
+            _ => return Some((10, CodeType::DateTime(date_time))),
+        };
+
+        let mut time = Time { separator, hour, minute, second: None, fraction_digits: 0, fraction: 0, zone: Zone::Local };
+        let mut length = 16;
+
+        if let (Some(b':'), Some(second)) = (s.get(16), self.digits(17, 2).filter(|second| *second <= 60)) {
+            time.second = Some(second as u8);
+            length = 19;
+
+            if s.get(19) == Some(&b'.') {
+                let digits = s[20..].iter().take(Time::MAX_FRACTION_DIGITS as usize).take_while(|ch| Self::is_digit(**ch)).count();
+
+                if digits > 0 {
+                    time.fraction_digits = digits as u8;
+                    time.fraction = self.digits(20, digits)?;
+                    length = 20 + digits;
+                }
+            }
+        }
+
+        if s.get(length) == Some(&b'Z') {
+            time.zone = Zone::Utc;
+            length += 1;
+        } else if let Some(sign @ (b'+' | b'-')) = s.get(length) {
+            let colon = s.get(length + 3) == Some(&b':');
+            let hours = self.digits(length + 1, 2).filter(|hours| *hours < 24);
+            let minutes = self.digits(length + if colon { 4 } else { 3 }, 2).filter(|minutes| *minutes < 60);
+
+            if let (Some(hours), Some(minutes)) = (hours, minutes) {
+                time.zone = Zone::Offset { negative: *sign == b'-', minutes: (hours * 60 + minutes) as u16, colon };
+                length += if colon { 6 } else { 5 };
+            }
+        }
+
+        date_time.time = Some(time);
+
+        Some((length, CodeType::DateTime(date_time)))
+    }
+
+    //Every date at the start of the string, with each shorter form of its time (such as without the zone, the fraction or the seconds), for the optimal parser.
+    //Any of these starts with the date, so there are none if try_date_time doesn't match
+    fn date_time_prefixes(&self) -> Vec<(usize, CodeType)> {
+        let longest = match self.try_date_time() {
+            Some((length, _)) => length,
+            None => return Vec::new(),
+        };
+
+        (10..=longest).filter_map(|length| self.sub(&self.main[..length]).try_date_time().filter(|(found, _)| *found == length)).collect()
+    }
+
+    //The number of digits in the run starting at `start`, counting no further than `limit` so long runs aren't scanned in full
+    fn digit_run(&self, start: usize, limit: usize) -> usize {
+        self.main.get(start..).map_or(0, |rest| rest.iter().take(limit).take_while(|ch| Self::is_digit(**ch)).count())
+    }
+
+    //Matches a port after an address, as in :8080, returning the port and the number of bytes it covers
+    fn try_port(&self, start: usize) -> Option<(u16, usize)> {
+
+        if self.main.get(start) != Some(&b':') {
+            return None;
+        }
+
+        let digits = self.digit_run(start + 1, 6);
+
+        //Leading zeros wouldn't be written back
+        if digits == 0 || digits > 5 || (digits > 1 && self.main[start + 1] == b'0') {
+            return None;
+        }
+
+        let port = u16::try_from(self.digits(start + 1, digits)?).ok()?;
+
+        Some((port, digits + 1))
+    }
+
+    //Matches an IPv4 address in dotted decimal at the start of the string, along with any port after it
+    fn try_ipv4(&self) -> Option<(usize, CodeType)> {
+
+        let mut octets = [0u8; 4];
+        let mut length = 0;
+
+        for (i, octet) in octets.iter_mut().enumerate() {
+            if i > 0 {
+                if self.main.get(length) != Some(&b'.') {
+                    return None;
+                }
+
+                length += 1;
+            }
+
+            let digits = self.digit_run(length, 4);
+
+            if digits == 0 || digits > 3 || (digits > 1 && self.main[length] == b'0') {
+                return None;
+            }
+
+            *octet = u8::try_from(self.digits(length, digits)?).ok()?;
+            length += digits;
+        }
+
+        //Longer dotted numbers (such as version numbers) aren't addresses
+        if self.main.get(length) == Some(&b'.') && self.digit_run(length + 1, 1) > 0 {
+            return None;
+        }
+
+        let port = self.try_port(length);
+
+        if let Some((_, port_length)) = port {
+            length += port_length;
+        }
+
+        Some((length, CodeType::Ipv4(octets, port.map(|(port, _)| port))))
+    }
+
+    //Matches an IPv6 address at the start of the string in any form Ipv6Format can write, or in brackets followed by a port
+    fn try_ipv6(&self) -> Option<(usize, CodeType)> {
+
+        let brackets = self.main.first() == Some(&b'[');
+        let start = brackets as usize;
+
+        //The longest address text, as in ffff:ffff:ffff:ffff:ffff:ffff:255.255.255.255
+        const LENGTH: usize = 45;
+
+        //Stopping the scan just past that length keeps a long run of hex digits and colons from being scanned again at every position
+        let rest = self.main.get(start..)?;
+        let mut end = rest.iter().take(LENGTH + 1).take_while(|ch| ch.is_ascii_hexdigit() || **ch == b':' || **ch == b'.').count();
+
+        if end > LENGTH {
+            return None;
+        }
+
+        //Every address has at least two colons
+        if rest[..end].iter().filter(|ch| **ch == b':').count() < 2 {
+            return None;
+        }
+
+        //A colon or dot straight after the address belongs to the text around it
+        let address = loop {
+            //The run is all ascii, so this never fails
+            if let Ok(address) = core::str::from_utf8(&rest[..end]).unwrap().parse::<core::net::Ipv6Addr>() {
+                break address;
+            }
+
+            if end == 0 || !matches!(rest[end - 1], b':' | b'.') {
+                return None;
+            }
+
+            end -= 1;
+        };
+
+        let mut length = start + end;
+        let mut port = None;
+
+        if brackets {
+            if self.main.get(length) != Some(&b']') {
+                return None;
+            }
+
+            let (number, port_length) = self.try_port(length + 1)?;
+
+            port = Some(number);
+            length += 1 + port_length;
+        }
+
+        let text = &rest[..end];
+
+        //Only forms that are written back exactly can be used
+        let format = Ipv6Format {
+            uppercase: text.iter().any(u8::is_ascii_uppercase),
+            expanded: text.len() == 39,
+            port,
+        };
+
+        let mut written = String::new();
+        format.write(address.octets(), & mut written).ok()?;
+
+        if written.as_bytes() != &self.main[..length] {
+            return None;
+        }
+
+        Some((length, CodeType::Ipv6(address.octets(), format)))
+    }
+
+    //Returns the longest run of each repeated sequence as (count, sequence length, index). The count is not limited to what a single code can hold
+    fn repetition_runs(&self) -> Vec<(usize, usize, usize)> {
+
+        let mut runs = Vec::new();
 
         for length in crate::map::REPETITION_LENGTHS {
             let mut sub = self.main;
@@ -96,22 +501,210 @@ impl<'a> CodeIterator<'a> {
             }
 
             if count > 3 {
+                runs.push((count, length, *ind.unwrap()));
+            }
+        }
+
+        runs
+    }
+
+    fn try_repetitions(&self) -> Option<(usize, Match)> {
+
+        let (count, length, index) = self.repetition_runs().into_iter().next()?;
+
+        //A single code can only hold so many repetitions, longer runs are split across several codes
+        let count = count.min(CodeType::REPETITION_COUNT - 1);
+
+        Some((count, Match {
+            index,
+            length: count * length,
+            space: false
+        }))
+    }
+
+
+
+    //Lists the codes that encode the start of the string as a capitalised or uppercase version of a two byte, three byte or custom entry.
+    //The lists only contain lowercase entries, so the start of the string is lowercased before looking it up
+    fn cased_candidates(&self) -> Vec<(usize, CodeType)> {
+
+        let mut candidates = Vec::new();
+
+        let space = self.main.first() == Some(&b' ');
+        let word = if space { &self.main[1..] } else { self.main };
+
+        //Quick check to avoid lowercasing anything when there's nothing to match
+        if !word.first().is_some_and(u8::is_ascii_uppercase) {
+            return candidates;
+        }
+
+        let consumed = |length| if space { length + 1 } else { length };
 
-                return Some((count, Match {
-                    index: *ind.unwrap(),
-                    length: count * length,
-                    space: false
-                }));
+        for length in self.engine.tables.lengths.iter().copied() {
+            if word.len() < length {
+                continue;
+            }
+
+            if let Some(case) = Case::detect(&word[..length]) {
+                let lower = word[..length].to_ascii_lowercase();
+
+                if let Some(index) = self.engine.tables.two_byte.index_of(&lower) {
+                    candidates.push((consumed(length), CodeType::Cased(case, Box::new(CodeType::TwoByteCommon(space, index)))));
+                }
+
+                if let Some(index) = self.engine.tables.three_byte.index_of(&lower) {
+                    candidates.push((consumed(length), CodeType::Cased(case, Box::new(CodeType::ThreeByteUncommon(space, index)))));
+                }
+            }
+        }
+
+        if space && !self.engine.custom_spaces {
+            return candidates;
+        }
+
+        for length in &self.engine.lengths {
+            if word.len() < *length {
+                continue;
+            }
+
+            if let Some(case) = Case::detect(&word[..*length]) {
+                if let Some(index) = self.engine.custom_map.get_by_left(&word[..*length].to_ascii_lowercase()) {
+                    candidates.push((consumed(*length), CodeType::Cased(case, Box::new(CodeType::Custom(space, *index)))));
+                }
             }
         }
 
-        None
+        candidates
     }
 
+    //The longest unicode window run that can encode the start of the string, if it starts with a character in a window.
+    //Gaps of ascii characters are only included if they are followed by another character in the window,
+    //and storing them in the run costs no more than ending the run and starting a new one after the gap
+    fn window_run(&self) -> Option<(usize, CodeType)> {
+
+        let s = self.text();
+
+        let window = Window::of(s.chars().next()?)?;
+
+        let mut run = Vec::new();
+        let mut length = 0;
+
+        while run.len() < u8::MAX as usize {
+            let rest = &s[length..];
+
+            let ch = match rest.chars().next() {
+                Some(ch) => ch,
+                None => break,
+            };
+
+            if let Some(byte) = Window::byte(window, ch).filter(|_| !ch.is_ascii()) {
+                run.push(byte);
+                length += ch.len_utf8();
+                continue;
+            }
+
+            if !ch.is_ascii() {
+                break;
+            }
+
+            let gap = rest.find(|c: char| !c.is_ascii()).unwrap_or(rest.len());
 
+            if rest[gap..].chars().next().and_then(Window::of) != Some(window) || run.len() + gap >= u8::MAX as usize {
+                break;
+            }
+
+            let (_, bytes) = self.sub(&rest.as_bytes()[..gap]).plain_cost(gap);
+
+            if gap > bytes + 4 {
+                break;
+            }
+
+            run.extend(rest.chars().take(gap).map(|ch| Window::byte(window, ch).unwrap()));
+            length += gap;
+        }
+
+        Some((length, CodeType::UnicodeWindow(window, run)))
+    }
+
+    //Every unicode window run that can encode the start of the string, ending on a character in the window
+    fn window_candidates(&self) -> Vec<(usize, CodeType)> {
+
+        let mut candidates = Vec::new();
+
+        let s = self.text();
+
+        let window = match s.chars().next().and_then(Window::of) {
+            Some(window) => window,
+            None => return candidates,
+        };
+
+        let mut run = Vec::new();
+        let mut length = 0;
+
+        for ch in s.chars() {
+            let byte = match Window::byte(window, ch) {
+                Some(byte) if run.len() < u8::MAX as usize => byte,
+                _ => break,
+            };
+
+            run.push(byte);
+            length += ch.len_utf8();
+
+            if !ch.is_ascii() {
+                candidates.push((length, CodeType::UnicodeWindow(window, run.clone())));
+            }
+        }
+
+        candidates
+    }
 
     fn encode_next(&self) -> (usize, CodeType) {
 
+        let (length, code) = self.encode_plain();
+
+        let cased = self.cased_candidates().into_iter().max_by(|(a_length, a), (b_length, b)| {
+            a_length.cmp(b_length).then(b.serialized_len().cmp(&a.serialized_len()))
+        });
+
+        //Only use a case modifier, unicode window, formatted number, hex code, date or address if it takes fewer bytes than the plain codes covering the same part of the string.
+        //If several do, use the one with the fewest bytes for each byte of the string it covers
+        let mut best: Option<(usize, CodeType)> = None;
+
+        for (alternative_length, alternative) in [cased, self.window_run(), self.try_formatted_number(), self.hex_run(), self.try_uuid(), self.try_date_time(), self.try_ipv4(), self.try_ipv6()].into_iter().flatten() {
+            let (covered, bytes) = self.plain_cost(alternative_length);
+
+            if alternative.serialized_len() * covered >= bytes * alternative_length {
+                continue;
+            }
+
+            if best.as_ref().is_none_or(|(best_length, best)| alternative.serialized_len() * best_length < best.serialized_len() * alternative_length) {
+                best = Some((alternative_length, alternative));
+            }
+        }
+
+        best.unwrap_or((length, code))
+    }
+
+    //Encodes at least `length` bytes of the string without case modifiers, returning the number of bytes covered and how many bytes that took
+    fn plain_cost(&self, length: usize) -> (usize, usize) {
+        let mut sub = self.sub(self.main);
+
+        let mut covered = 0;
+        let mut bytes = 0;
+
+        while covered < length {
+            let (consumed, code) = sub.encode_plain();
+
+            sub.main = &sub.main[consumed..];
+            covered += consumed;
+            bytes += code.serialized_len();
+        }
+
+        (covered, bytes)
+    }
+
+    fn encode_plain(&self) -> (usize, CodeType) {
+
         //Basically the aim of this function is to pick the best way to encode the next chunk of bytes.
         //We use try_wonder, try_common and try_uncommon to create 3 possible types of encoding.
         //We then pick the most compact version (if all 3 work equally well, we pick the version that matches the largest string)
@@ -137,17 +730,17 @@ impl<'a> CodeIterator<'a> {
         // We try and match from all 3 maps together, starting from the largest length
         // We start with the largest length and the smallest map, this should mean
         // our result has the best length/cost ratio
-        for length in crate::map::TOTAL_LENGTHS {
+        for length in self.engine.tables.lengths.iter().copied() {
 
-            if let Some(m) = crate::map::OneByteMap::get_map().match_spaced_sequence(self.main, false, length) {
+            if let Some(m) = self.engine.tables.one_byte.match_spaced_sequence(self.main, false, length) {
                 return (m.length, CodeType::OneByteWonder(m.index))
             }
 
-            if let Some(m) = crate::map::TwoByteMap::get_map().match_spaced_sequence(self.main, true, length) {
+            if let Some(m) = self.engine.tables.two_byte.match_spaced_sequence(self.main, true, length) {
                 return (m.length, CodeType::TwoByteCommon(m.space, m.index))
             }
 
-            if let Some(m) = crate::map::ThreeByteMap::get_map().match_spaced_sequence(self.main, true, length) {
+            if let Some(m) = self.engine.tables.three_byte.match_spaced_sequence(self.main, true, length) {
                 return (m.length, CodeType::ThreeByteUncommon(m.space, m.index))
             }
 
@@ -155,14 +748,18 @@ impl<'a> CodeIterator<'a> {
 
         //3. Try and match a unicode character
         {
-            let s = unsafe { from_utf8_unchecked(self.main) }; //We can use unchecked here because we will always start at a unicode boundary
-            if let Some(first) = s.chars().nth(0) {
+            if let Some(first) = self.text().chars().nth(0) {
                 if !first.is_ascii() {
                     return (first.len_utf8(), CodeType::UnicodeChar(first))
                 }
             }
         }
 
+        //Any other non-ascii byte isn't valid utf-8, which can only happen when compressing bytes
+        if !self.main[0].is_ascii() {
+            return (1, CodeType::RawByte(self.main[0]))
+        }
+
 
         //8. Try and match one of the non-printables
         if let Some(index) = crate::map::Controls::get_map().get(& [self.main[0]]) {
@@ -174,6 +771,66 @@ impl<'a> CodeIterator<'a> {
 
 
     }
+
+    //Lists every code that can encode the start of the string, along with the number of bytes each one consumes.
+    //Used by the optimal parser, which (unlike encode_next) considers all of them rather than picking the first that works
+    pub (crate) fn candidates(&self) -> Vec<(usize, CodeType)> {
+
+        let mut candidates = Vec::new();
+
+        for length in &self.engine.lengths {
+            for m in self.engine.custom_map.match_all_sequences(self.main, self.engine.custom_spaces, *length) {
+                candidates.push((m.length, CodeType::Custom(m.space, m.index)));
+            }
+        }
+
+        for (count, length, index) in self.repetition_runs() {
+            for count in 4..=count.min(CodeType::REPETITION_COUNT - 1) {
+                candidates.push((count * length, CodeType::Repetitions(count as u32, index)));
+            }
+        }
+
+        //Stopping a number, hex run or date early can leave a cheaper code for what follows, so every prefix is a candidate
+        candidates.extend(self.number_prefixes());
+        candidates.extend(self.formatted_numbers());
+        candidates.extend(self.hex_runs());
+        candidates.extend(self.try_uuid());
+        candidates.extend(self.date_time_prefixes());
+        candidates.extend(self.try_ipv4());
+        candidates.extend(self.try_ipv6());
+
+        for length in self.engine.tables.lengths.iter().copied().chain([1]) {
+
+            for m in self.engine.tables.one_byte.match_all_sequences(self.main, false, length) {
+                candidates.push((m.length, CodeType::OneByteWonder(m.index)));
+            }
+
+            for m in self.engine.tables.two_byte.match_all_sequences(self.main, true, length) {
+                candidates.push((m.length, CodeType::TwoByteCommon(m.space, m.index)));
+            }
+
+            for m in self.engine.tables.three_byte.match_all_sequences(self.main, true, length) {
+                candidates.push((m.length, CodeType::ThreeByteUncommon(m.space, m.index)));
+            }
+        }
+
+        if let Some(first) = self.text().chars().next() {
+            if !first.is_ascii() {
+                candidates.push((first.len_utf8(), CodeType::UnicodeChar(first)));
+            }
+        } else if !self.main[0].is_ascii() {
+            candidates.push((1, CodeType::RawByte(self.main[0])));
+        }
+
+        if let Some(index) = crate::map::Controls::get_map().get(& [self.main[0]]) {
+            candidates.push((1, CodeType::Unprintable(*index)));
+        }
+
+        candidates.extend(self.cased_candidates());
+        candidates.extend(self.window_candidates());
+
+        candidates
+    }
 }
 
 impl Iterator for CodeIterator<'_> {
@@ -181,7 +838,7 @@ impl Iterator for CodeIterator<'_> {
 
     fn next(&mut self) -> Option<Self::Item> {
 
-        if self.main.len() == 0 {
+        if self.main.is_empty() {
             return None;
         }
 
@@ -193,3 +850,20 @@ impl Iterator for CodeIterator<'_> {
     }
 }
 
+
+//The code stream for a string, produced using whichever parse strategy the engine was built with
+pub (crate) enum Codes<'a> {
+    Greedy(CodeIterator<'a>),
+    Optimal(alloc::vec::IntoIter<CodeType>),
+}
+
+impl Iterator for Codes<'_> {
+    type Item = CodeType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Codes::Greedy(iter) => iter.next(),
+            Codes::Optimal(iter) => iter.next(),
+        }
+    }
+}
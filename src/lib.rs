@@ -3,7 +3,10 @@
 //!
 //! See [readme](https://github.com/ray33ee/byte-size/blob/main/readme.md) for more information
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
 extern crate core;
+extern crate alloc;
 
 mod ir;
 mod iterator;
@@ -11,6 +14,9 @@ mod tests;
 mod serde;
 mod map;
 mod bi;
+mod optimal;
+mod header;
+mod cursor;
 
 ///Contains the `Builder` struct used to construct `Engine`s
 pub mod builder;
@@ -21,10 +27,27 @@ pub mod engine;
 ///Contains all possible error types raised by the decompressor
 pub mod error;
 
+///Contains the `FramedReader` struct used to read concatenated records
+pub mod framed;
+
+///Contains the `Token` struct used to explain how a string is compressed
+pub mod explain;
+
+///Contains the `Tables` struct used to load the dictionary tables at runtime
+pub mod tables;
+
+///Contains the `CompressedStr` struct used to keep strings compressed in memory
+pub mod compressed;
+
+///Contains the `Trainer` struct used to choose custom strings from a sample corpus
+pub mod train;
+
 ///Convenience function to compress using default options
 pub use crate::engine::compress;
 
 ///Convenience function to compress using default options
 pub use crate::engine::decompress;
 
-pub use crate::error::Result;
\ No newline at end of file
+pub use crate::error::Result;
+
+pub use crate::map::LANGUAGE;
\ No newline at end of file
@@ -0,0 +1,326 @@
+//! Command line tool for compressing, decompressing and inspecting strings with byte-size
+
+use std::fs::read_to_string;
+use std::io::{Read, Write};
+use byte_size::builder::{Builder, ParseStrategy};
+use byte_size::engine::Engine;
+use byte_size::train::Trainer;
+
+const USAGE: &str = "Usage: byte-size <COMMAND> [OPTIONS] [INPUT...]
+
+Commands:
+    compress      Compress the input strings
+    decompress    Decompress the input bytes
+    explain       Show the codes used to compress the input strings
+    train         Choose custom strings for the corpus files given as input, one string per line
+
+Input is taken from the arguments (joined with spaces), or from stdin if there are none.
+
+Options:
+    -l, --lines            Treat each line of the input as a separate string. Arguments are read as file paths
+        --hex              Compressed bytes are written/read as hex (default)
+        --base64           Compressed bytes are written/read as base64
+        --raw              Compressed bytes are written/read as raw bytes (not with --lines)
+    -c, --custom <FILE>    Use the custom strings in FILE (one per line) instead of the default list
+        --custom-spaces    Enable custom spaces
+        --optimal          Use the optimal parse strategy when compressing
+        --header           Write/read the format header
+    -h, --help             Print this message";
+
+#[derive(PartialEq, Clone, Copy)]
+enum Format {
+    Hex,
+    Base64,
+    Raw,
+}
+
+#[derive(Clone)]
+struct Options {
+    command: String,
+    lines: bool,
+    format: Format,
+    custom: Option<String>,
+    custom_spaces: bool,
+    optimal: bool,
+    header: bool,
+    inputs: Vec<String>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let command = args.next().ok_or("No command given")?;
+
+    let mut options = Options {
+        command,
+        lines: false,
+        format: Format::Hex,
+        custom: None,
+        custom_spaces: false,
+        optimal: false,
+        header: false,
+        inputs: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" | "--lines" => options.lines = true,
+            "--hex" => options.format = Format::Hex,
+            "--base64" => options.format = Format::Base64,
+            "--raw" => options.format = Format::Raw,
+            "-c" | "--custom" => options.custom = Some(args.next().ok_or("--custom expects a file")?),
+            "--custom-spaces" => options.custom_spaces = true,
+            "--optimal" => options.optimal = true,
+            "--header" => options.header = true,
+            "-h" | "--help" => options.command = String::from("help"),
+            "--" => options.inputs.extend(&mut args),
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("Unknown option '{}'", arg)),
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    if options.lines && options.format == Format::Raw {
+        return Err(String::from("--raw can't be used with --lines"));
+    }
+
+    Ok(options)
+}
+
+fn engine(options: &Options) -> Result<Engine, String> {
+    let mut builder = Builder::default();
+
+    if let Some(path) = &options.custom {
+        let custom = read_to_string(path).map_err(|e| format!("Could not read '{}': {}", path, e))?;
+
+        builder.set_custom(custom.lines().map(String::from));
+    }
+
+    builder
+        .set_custom_spaces(options.custom_spaces)
+        .set_header(options.header)
+        .set_parse_strategy(if options.optimal { ParseStrategy::Optimal } else { ParseStrategy::Greedy });
+
+    Ok(builder.engine())
+}
+
+//Reads the input as a list of strings, one per line with --lines, otherwise a single string
+fn read_strings(options: &Options) -> Result<Vec<String>, String> {
+    let text = if options.lines && !options.inputs.is_empty() {
+        let mut text = String::new();
+
+        for path in &options.inputs {
+            text.push_str(&read_to_string(path).map_err(|e| format!("Could not read '{}': {}", path, e))?);
+
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+
+        text
+    } else if !options.inputs.is_empty() {
+        return Ok(vec![options.inputs.join(" ")]);
+    } else {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|e| format!("Could not read stdin: {}", e))?;
+        text
+    };
+
+    if options.lines {
+        Ok(text.lines().map(String::from).collect())
+    } else {
+        //A single trailing newline is almost always added by the shell, rather than being part of the string
+        Ok(vec![text.strip_suffix('\n').unwrap_or(&text).to_string()])
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<_> = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if digits.len() % 2 != 0 {
+        return Err(String::from("Hex input has an odd number of digits"));
+    }
+
+    digits.chunks(2).map(|pair| {
+        let pair: String = pair.iter().collect();
+        u8::from_str_radix(&pair, 16).map_err(|_| format!("Invalid hex '{}'", pair))
+    }).collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+fn from_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut n = 0u32;
+    let mut bits = 0;
+
+    for c in text.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE64.iter().position(|b| *b as char == c).ok_or(format!("Invalid base64 character '{}'", c))?;
+
+        n = n << 6 | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn encode(bytes: &[u8], format: Format) -> String {
+    match format {
+        Format::Hex | Format::Raw => to_hex(bytes),
+        Format::Base64 => to_base64(bytes),
+    }
+}
+
+fn decode(text: &str, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Hex | Format::Raw => from_hex(text),
+        Format::Base64 => from_base64(text),
+    }
+}
+
+fn compress(options: &Options) -> Result<(), String> {
+    let engine = engine(options)?;
+    let mut stdout = std::io::stdout().lock();
+
+    for string in read_strings(options)? {
+        let bytes = engine.compress(&string);
+
+        if options.format == Format::Raw {
+            stdout.write_all(&bytes)
+        } else {
+            writeln!(stdout, "{}", encode(&bytes, options.format))
+        }.map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn decompress(options: &Options) -> Result<(), String> {
+    let engine = engine(options)?;
+
+    let inputs = if options.format == Format::Raw {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).map_err(|e| format!("Could not read stdin: {}", e))?;
+        vec![bytes]
+    } else {
+        read_strings(options)?.iter().map(|text| decode(text, options.format)).collect::<Result<_, _>>()?
+    };
+
+    let mut stdout = std::io::stdout().lock();
+
+    for bytes in inputs {
+        let string = engine.decompress(&bytes).map_err(|e| e.to_string())?;
+
+        writeln!(stdout, "{}", string).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn explain(options: &Options) -> Result<(), String> {
+    let engine = engine(options)?;
+
+    for string in read_strings(options)? {
+        let bytes = engine.compress(&string);
+
+        println!("{:?}", string);
+        println!("    Original size:   {}", string.len());
+        println!("    Compressed size: {}", bytes.len());
+        println!("    Compressed:      {}", encode(&bytes, options.format));
+
+        for token in engine.explain(&string) {
+            let entry = match &token.entry {
+                Some(entry) => format!("{}{:?}", if token.space { "space + " } else { "" }, entry),
+                None => format!("{:?}", &string[token.span.clone()]),
+            };
+
+            println!("    {:>3}..{:<3} {:<18} {:<30} {}", token.span.start, token.span.end, format!("{:?}", token.kind), entry, encode(&token.bytes, options.format));
+        }
+    }
+
+    Ok(())
+}
+
+fn train(options: &Options) -> Result<(), String> {
+    let mut corpus = read_strings(&Options { lines: true, ..options.clone() })?;
+    corpus.retain(|line| !line.is_empty());
+
+    let custom = Trainer::default().set_custom_spaces(options.custom_spaces).select(corpus.iter().map(|s| s.as_str()));
+
+    //Printed one per line, so the output can be passed straight back in with --custom
+    for string in custom {
+        println!("{}", string);
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(std::env::args().skip(1))?;
+
+    match options.command.as_str() {
+        "compress" => compress(&options),
+        "decompress" => decompress(&options),
+        "explain" => explain(&options),
+        "train" => train(&options),
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        command => Err(format!("Unknown command '{}'", command)),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("byte-size: {}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar", &[0, 255, 128, 7]] {
+            assert_eq!(from_base64(&to_base64(bytes)).unwrap(), bytes);
+        }
+
+        assert_eq!(to_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(to_base64(b"fooba"), "Zm9vYmE=");
+        assert!(from_base64("Zm9v!").is_err());
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("00 0f ff").unwrap(), [0, 15, 255]);
+        assert!(from_hex("0f0").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::engine::Engine;
+use crate::ir::CodeType;
+use crate::iterator::CodeIterator;
+
+//Finds the sequence of codes with the smallest total serialized size.
+//
+//This is a shortest path problem over byte positions in the string, where each candidate code at a position is an edge
+//to the position just after it. We solve it backwards, so cost[i] is the smallest number of bytes needed to encode bytes[i..].
+//If `utf8` is true the bytes must be valid utf-8, otherwise bytes that aren't are encoded as raw bytes
+pub (crate) fn optimal_codes(bytes: & [u8], utf8: bool, engine: & Engine) -> Vec<CodeType> {
+
+    let length = bytes.len();
+
+    let mut cost = vec![usize::MAX; length + 1];
+    let mut choice: Vec<Option<(usize, CodeType)>> = (0..=length).map(|_| None).collect();
+
+    cost[length] = 0;
+
+    for i in (0..length).rev() {
+
+        //Codes always start and end on a char boundary (utf-8 continuation bytes are 0b10xxxxxx)
+        if utf8 && bytes[i] & 0xc0 == 0x80 {
+            continue;
+        }
+
+        for (consumed, code) in CodeIterator::new(&bytes[i..], utf8, engine).candidates() {
+
+            let rest = cost[i + consumed];
+
+            if rest == usize::MAX {
+                continue;
+            }
+
+            let total = rest + code.serialized_len();
+
+            //Candidates are listed in order of preference, so only replace on a strict improvement
+            if total < cost[i] {
+                cost[i] = total;
+                choice[i] = Some((consumed, code));
+            }
+        }
+    }
+
+    let mut codes = Vec::new();
+    let mut i = 0;
+
+    while i < length {
+        let (consumed, code) = choice[i].take().expect("Every char boundary has at least one candidate code, and every byte does when compressing bytes");
+
+        codes.push(code);
+        i += consumed;
+    }
+
+    codes
+}
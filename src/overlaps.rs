@@ -0,0 +1,5 @@
+//Three byte entries that are also two byte entries, so would never be used, but are allowed anyway. They are at the start of the
+//built in English three byte list, and are kept as removing them would change the code of every three byte entry after them.
+//Each is the entry and its line in the three byte list, starting at 1. This file is included by both build.rs and src/tables.rs,
+//so lists are checked the same way whether they are built in or loaded at runtime
+const KNOWN_OVERLAPS: [(& str, usize); 3] = [("their", 1), ("forum", 2), ("forms", 3)];
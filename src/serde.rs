@@ -1,136 +1,552 @@
+#[cfg(feature = "std")]
 use std::io::Read;
+use crate::cursor::Source;
 use crate::engine::Engine;
-use crate::ir::CodeType;
-use crate::error::Result;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use crate::ir::{Case, CodeType, DateTime, Ipv6Format, NumberFormat, Time, Zone};
+use crate::error::{Error, Result};
 
 impl CodeType {
 
-    const ONE_BYTE_WONDER_COUNT: usize = crate::map::OneByteMap::get_length();
+    //The code space is laid out for the largest tables allowed, so shorter two and three byte lists leave some codes unused
+    const ONE_BYTE_WONDER_COUNT: usize = crate::tables::ONE_BYTE_LENGTH;
     const CUSTOM_COUNT: usize = 32;
-    const REPETITION_COUNT: usize = 32;
+    pub (crate) const REPETITION_COUNT: usize = 32;
+    //Repetitions of fewer than 4 units are never used, so the first repetition counts are given other meanings
+    const CAPITALISE: usize = 0;
+    const UPPERCASE: usize = 1;
+    const UNICODE_WINDOW: usize = 2;
     const NUMBER_COUNT: usize = 32;
     const UNICODE_COUNT: usize = 1; //Unicode only takes one value out of the one bytes
+    //Ascii characters are all one byte wonders, so they never follow the unicode marker. Bytes below 0x80 start formatted numbers instead,
+    //with the sign, separator style and decimals in the bits of the byte. NUMBER_ZEROS is set if a byte with the number of leading zeros follows,
+    //and the digits come last as a varint
+    const NUMBER_NEGATIVE: u8 = 0x01;
+    const NUMBER_ZEROS: u8 = 0x40;
+    //Bytes that can't start a utf-8 sequence can follow the unicode marker instead, to store bytes that aren't valid utf-8.
+    //Continuation bytes (0x80 to 0xBF) are stored as themselves, the other non-ascii bytes after RAW_BYTE, which is never valid utf-8
+    const RAW_BYTE: u8 = 0xc0;
+    //Bytes from 0xF5 up can't start a utf-8 sequence either, so after the unicode marker they start the codes below. Every value of the first byte is taken,
+    //so new kinds of code start this way too, with 0xC1 (which is never valid utf-8) and 0xFD to 0xFF still free.
+    //UUIDs and runs of hex digits have codes for lowercase and uppercase letters
+    const UUID: u8 = 0xf5;
+    const UUID_UPPERCASE: u8 = 0xf6;
+    const HEX: u8 = 0xf7;
+    const HEX_UPPERCASE: u8 = 0xf8;
+    //Followed by a byte giving the shape of a date and time, then its fields packed together (see date_time_radices).
+    //The shape has bits for a time and a space separator, the zone in bits 2 and 3, and whether there are seconds and how many digits follow them in the top bits
+    const DATE_TIME: u8 = 0xf9;
+    const DATE_TIME_TIME: u8 = 0x01;
+    const DATE_TIME_SPACE: u8 = 0x02;
+    //Followed by the four bytes of an IPv4 address, then the port (if there is one)
+    const IPV4: u8 = 0xfa;
+    const IPV4_PORT: u8 = 0xfb;
+    //Followed by a byte of IPV6_* flags, the port (if there is one) and then the 16 bytes of the address
+    const IPV6: u8 = 0xfc;
+    const IPV6_UPPERCASE: u8 = 0x01;
+    const IPV6_EXPANDED: u8 = 0x02;
+    const IPV6_PORT: u8 = 0x04;
     const NON_PRINTABLE_COUNT: usize = crate::map::Controls::get_length();
-    const TWO_BYTE_COUNT: usize = crate::map::TwoByteMap::get_length();
-    const THREE_BYTE_COUNT: usize = crate::map::ThreeByteMap::get_length();
+    const TWO_BYTE_COUNT: usize = crate::tables::TWO_BYTE_MAX;
+    const THREE_BYTE_COUNT: usize = crate::tables::THREE_BYTE_MAX;
 
-    pub (crate) fn deserialize_from<R: Read>(mut reader: R, engine: &Engine) -> Result<Self> {
-
-        let first: u8 = bincode::deserialize_from(& mut reader)?;
+    ///Reads the rest of the code starting with the byte `first` from the source, making sure every index it contains is valid.
+    ///
+    /// `offset` is the position of the code in the compressed bytes and is only used for error reporting
+    pub (crate) fn deserialize_from<S: Source>(first: u8, source: & mut S, engine: &Engine, offset: usize) -> Result<Self> {
 
         Ok(if first < Self::ONE_BYTE_WONDER_COUNT as u8 {
             CodeType::OneByteWonder(first as usize)
         } else if first == Self::ONE_BYTE_WONDER_COUNT as u8 {
             //Unicode
 
-            let ch: char = bincode::deserialize_from(& mut reader)?;
+            let lead = source.read_byte()?;
 
-            CodeType::UnicodeChar(ch)
-        } else {
-            let obw_index = first as usize - Self::ONE_BYTE_WONDER_COUNT - Self::UNICODE_COUNT;
+            match lead {
+                0x00..=0x7f => {
+                    let zeros = if lead & Self::NUMBER_ZEROS != 0 {
+                        match source.read_byte()? {
+                            0 => return Err(Error::InvalidCode { offset }),
+                            zeros => zeros,
+                        }
+                    } else {
+                        0
+                    };
+
+                    let format = NumberFormat {
+                        negative: lead & Self::NUMBER_NEGATIVE != 0,
+                        style: (lead >> 1) & 0x03,
+                        decimals: (lead >> 3) & 0x07,
+                        zeros,
+                    };
+
+                    CodeType::FormattedNumber(format, read_varint(source, offset)?)
+                }
+                0x80..=0xbf => CodeType::RawByte(lead),
+                Self::RAW_BYTE => {
+                    let byte = source.read_byte()?;
 
-            let second: u8 = bincode::deserialize_from(& mut reader)?;
+                    //Continuation bytes have their own shorter code
+                    if byte < Self::RAW_BYTE {
+                        return Err(Error::InvalidCode { offset });
+                    }
 
-            let two_code = obw_index as usize * 256usize + second as usize;
+                    CodeType::RawByte(byte)
+                }
+                Self::UUID | Self::UUID_UPPERCASE => {
+                    let mut bytes = [0u8; 16];
 
-            if two_code < Self::TWO_BYTE_COUNT*2 {
-                CodeType::TwoByteCommon(two_code / Self::TWO_BYTE_COUNT != 0, two_code % Self::TWO_BYTE_COUNT)
-            } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT {
+                    for byte in & mut bytes {
+                        *byte = source.read_byte()?;
+                    }
 
-                let comb = two_code - Self::TWO_BYTE_COUNT*2;
+                    CodeType::Uuid(lead == Self::UUID_UPPERCASE, bytes)
+                }
+                Self::HEX | Self::HEX_UPPERCASE => {
+                    let digits = source.read_byte()?;
 
-                let (space, comb) = if !engine.custom_spaces {
-                    (false, comb)
-                } else {
-                    (comb >= 16, if comb >= 16 {comb - 16} else {comb})
-                };
+                    if digits == 0 {
+                        return Err(Error::InvalidCode { offset });
+                    }
 
-                CodeType::Custom(space, comb as usize)
-            } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT {
+                    let length = (digits as usize).div_ceil(2);
+                    let mut bytes = Vec::with_capacity(length);
 
-                let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT;
+                    for _ in 0..length {
+                        bytes.push(source.read_byte()?);
+                    }
 
-                let third: u8 = bincode::deserialize_from(& mut reader)?;
+                    //An odd number of digits leaves the low half of the last byte unused, which must be zero
+                    if digits % 2 == 1 && bytes[bytes.len() - 1] & 0x0f != 0 {
+                        return Err(Error::InvalidCode { offset });
+                    }
 
-                CodeType::Repetitions(comb as u32, third as usize)
-            } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT {
-                let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT;
+                    CodeType::Hex(lead == Self::HEX_UPPERCASE, digits, bytes)
+                }
+                Self::DATE_TIME => {
+                    let shape = source.read_byte()?;
+                    let precision = shape >> 4;
+
+                    //A date on its own has no other bits set
+                    if (shape & Self::DATE_TIME_TIME == 0 && shape != 0) || precision > Time::MAX_FRACTION_DIGITS + 1 {
+                        return Err(Error::InvalidCode { offset });
+                    }
 
-                let four = comb / 8;
-                let len = comb % 8 + 1;
+                    let radices = date_time_radices(shape);
+                    let mut packed = 0u128;
 
-                let mut num = four as u128;
+                    for i in 0..packed_length(&radices) {
+                        packed |= (source.read_byte()? as u128) << (i * 8);
+                    }
+
+                    if packed >= radices.iter().product() {
+                        return Err(Error::InvalidCode { offset });
+                    }
 
-                for i in 0..len {
-                    let byte: u8 = bincode::deserialize_from(& mut reader)?;
+                    let mut fields = [0u128; 9];
 
-                    num += (byte as u128) << (i*8+2)
+                    for (field, radix) in fields.iter_mut().zip(radices).rev() {
+                        *field = packed % radix;
+                        packed /= radix;
+                    }
+
+                    let [year, month, day, hour, minute, second, fraction, negative, minutes] = fields;
+
+                    let zone = match (shape >> 2) & 0x03 {
+                        0 => Zone::Local,
+                        1 => Zone::Utc,
+                        zone => Zone::Offset { negative: negative != 0, minutes: minutes as u16, colon: zone == 2 },
+                    };
+
+                    let time = Time {
+                        separator: if shape & Self::DATE_TIME_SPACE != 0 { ' ' } else { 'T' },
+                        hour: hour as u8,
+                        minute: minute as u8,
+                        second: if precision > 0 { Some(second as u8) } else { None },
+                        fraction_digits: precision.saturating_sub(1),
+                        fraction: fraction as u32,
+                        zone,
+                    };
+
+                    CodeType::DateTime(DateTime {
+                        year: year as u16,
+                        month: month as u8 + 1,
+                        day: day as u8 + 1,
+                        time: if shape & Self::DATE_TIME_TIME != 0 { Some(time) } else { None },
+                    })
                 }
+                Self::IPV4 | Self::IPV4_PORT => {
+                    let mut octets = [0u8; 4];
 
-                CodeType::Number(num)
-            } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT + Self::NON_PRINTABLE_COUNT {
-                let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT - Self::NUMBER_COUNT;
+                    for octet in & mut octets {
+                        *octet = source.read_byte()?;
+                    }
 
-                CodeType::Unprintable(comb)
+                    let port = if lead == Self::IPV4_PORT { Some(read_port(source)?) } else { None };
+
+                    CodeType::Ipv4(octets, port)
+                }
+                Self::IPV6 => {
+                    let flags = source.read_byte()?;
+
+                    if flags & !(Self::IPV6_UPPERCASE | Self::IPV6_EXPANDED | Self::IPV6_PORT) != 0 {
+                        return Err(Error::InvalidCode { offset });
+                    }
+
+                    let port = if flags & Self::IPV6_PORT != 0 { Some(read_port(source)?) } else { None };
+
+                    let mut octets = [0u8; 16];
+
+                    for octet in & mut octets {
+                        *octet = source.read_byte()?;
+                    }
+
+                    CodeType::Ipv6(octets, Ipv6Format { uppercase: flags & Self::IPV6_UPPERCASE != 0, expanded: flags & Self::IPV6_EXPANDED != 0, port })
+                }
+                _ => CodeType::UnicodeChar(source.read_char_from(lead)?),
+            }
+        } else {
+            let second = source.read_byte()?;
+
+            Self::deserialize_two_byte(first, second, source, engine, offset)?
+        })
+
+
+    }
+
+    //The position of a two byte code in the two byte code space
+    fn two_byte_code(first: u8, second: u8) -> usize {
+        (first as usize - Self::ONE_BYTE_WONDER_COUNT - Self::UNICODE_COUNT) * 256usize + second as usize
+    }
+
+    //Deserializes a code whose first two bytes have already been read, where the first is past the unicode marker
+    fn deserialize_two_byte<S: Source>(first: u8, second: u8, source: & mut S, engine: &Engine, offset: usize) -> Result<Self> {
+
+        let two_code = Self::two_byte_code(first, second);
+
+        Ok(if two_code < Self::TWO_BYTE_COUNT*2 {
+            let index = two_code % Self::TWO_BYTE_COUNT;
+
+            if index >= engine.tables.two_byte.len() {
+                return Err(Error::InvalidCode { offset });
+            }
+
+            CodeType::TwoByteCommon(two_code / Self::TWO_BYTE_COUNT != 0, index)
+        } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT {
+
+            let comb = two_code - Self::TWO_BYTE_COUNT*2;
+
+            let (space, comb) = if !engine.custom_spaces {
+                (false, comb)
             } else {
-                let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT - Self::NUMBER_COUNT - Self::NON_PRINTABLE_COUNT;
+                (comb >= 16, if comb >= 16 {comb - 16} else {comb})
+            };
+
+            if engine.custom_map.get_by_right(&comb).is_none() {
+                return Err(Error::InvalidCustomIndex { index: comb, offset });
+            }
+
+            CodeType::Custom(space, comb)
+        } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT {
+
+            let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT;
+
+            if comb == Self::CAPITALISE || comb == Self::UPPERCASE {
+                let case = if comb == Self::CAPITALISE { Case::Capitalise } else { Case::Uppercase };
+
+                //Case modifiers can only be applied to words, which all start with a two byte code
+                let next = source.read_byte()?;
+
+                if next <= Self::ONE_BYTE_WONDER_COUNT as u8 {
+                    return Err(Error::InvalidCode { offset });
+                }
+
+                let second = source.read_byte()?;
+
+                //A case modifier can't modify another one, so a long run of them can't recurse without bound
+                let next_comb = Self::two_byte_code(next, second).wrapping_sub(Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT);
+
+                if next_comb == Self::CAPITALISE || next_comb == Self::UPPERCASE {
+                    return Err(Error::InvalidCode { offset });
+                }
+
+                let code = Self::deserialize_two_byte(next, second, source, engine, offset + 2)?;
+
+                if !matches!(code, CodeType::TwoByteCommon(_, _) | CodeType::ThreeByteUncommon(_, _) | CodeType::Custom(_, _)) {
+                    return Err(Error::InvalidCode { offset });
+                }
+
+                return Ok(CodeType::Cased(case, Box::new(code)));
+            }
+
+            if comb == Self::UNICODE_WINDOW {
+                let window = source.read_byte()?;
+                let length = source.read_byte()?;
+
+                if length == 0 {
+                    return Err(Error::InvalidCode { offset });
+                }
+
+                let mut run = Vec::with_capacity(length as usize);
+
+                for _ in 0..length {
+                    run.push(source.read_byte()?);
+                }
+
+                return Ok(CodeType::UnicodeWindow(window, run));
+            }
+
+            let third = source.read_byte()?;
+
+            if third as usize >= crate::map::Repetitions::get_length() {
+                return Err(Error::InvalidRepetitionIndex { index: third as usize, offset });
+            }
+
+            CodeType::Repetitions(comb as u32, third as usize)
+        } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT {
+            let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT;
+
+            let four = comb / 8;
+            let len = comb % 8 + 1;
 
-                let third: u8 = bincode::deserialize_from(& mut reader)?;
+            let mut num = four as u128;
 
-                let three_code = comb * 256 + third as usize;
+            for i in 0..len {
+                let byte = source.read_byte()?;
 
-                CodeType::ThreeByteUncommon(three_code / Self::THREE_BYTE_COUNT != 0, three_code % Self::THREE_BYTE_COUNT)
+                num += (byte as u128) << (i*8+2)
             }
 
+            CodeType::Number(num)
+        } else if two_code < Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT + Self::NON_PRINTABLE_COUNT {
+            let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT - Self::NUMBER_COUNT;
+
+            CodeType::Unprintable(comb)
+        } else {
+            let comb = two_code - Self::TWO_BYTE_COUNT*2 - Self::CUSTOM_COUNT - Self::REPETITION_COUNT - Self::NUMBER_COUNT - Self::NON_PRINTABLE_COUNT;
+
+            let third = source.read_byte()?;
+
+            let three_code = comb * 256 + third as usize;
+
+            //There are more three byte codes than there are uncommon words, so make sure this one is in range
+            if three_code >= Self::THREE_BYTE_COUNT*2 {
+                return Err(Error::InvalidCode { offset });
+            }
+
+            let index = three_code % Self::THREE_BYTE_COUNT;
+
+            if index >= engine.tables.three_byte.len() {
+                return Err(Error::InvalidCode { offset });
+            }
+
+            CodeType::ThreeByteUncommon(three_code / Self::THREE_BYTE_COUNT != 0, index)
         })
+    }
 
+    //The byte after DATE_TIME
+    fn date_time_shape(date_time: & DateTime) -> u8 {
+        let time = match &date_time.time {
+            Some(time) => time,
+            None => return 0,
+        };
+
+        let zone = match time.zone {
+            Zone::Local => 0,
+            Zone::Utc => 1,
+            Zone::Offset { colon, .. } => if colon { 2 } else { 3 },
+        };
+
+        let precision = match time.second {
+            Some(_) => 1 + time.fraction_digits,
+            None => 0,
+        };
+
+        Self::DATE_TIME_TIME | if time.separator == ' ' { Self::DATE_TIME_SPACE } else { 0 } | (zone << 2) | (precision << 4)
+    }
+
+    ///Number of bytes the code takes up once serialized
+    pub (crate) fn serialized_len(&self) -> usize {
+        match self {
+            CodeType::OneByteWonder(_) => 1,
+            CodeType::UnicodeChar(c) => Self::UNICODE_COUNT + c.len_utf8(),
+            CodeType::RawByte(byte) => if *byte < Self::RAW_BYTE { 2 } else { 3 },
+            CodeType::Hex(_, _, bytes) => 3 + bytes.len(),
+            CodeType::Uuid(_, bytes) => 2 + bytes.len(),
+            CodeType::DateTime(date_time) => 3 + packed_length(&date_time_radices(Self::date_time_shape(date_time))),
+            CodeType::Ipv4(octets, port) => 2 + octets.len() + if port.is_some() { 2 } else { 0 },
+            CodeType::Ipv6(octets, format) => 3 + octets.len() + if format.port.is_some() { 2 } else { 0 },
+            CodeType::TwoByteCommon(_, _) | CodeType::Custom(_, _) | CodeType::Unprintable(_) => 2,
+            CodeType::Repetitions(_, _) | CodeType::ThreeByteUncommon(_, _) => 3,
+            CodeType::Number(num) => {
+                //Two bytes for the code, then however many bytes it takes to store the number (without the bottom two bits)
+                let bits = 128 - (num >> 2).leading_zeros() as usize;
+
+                2 + bits.div_ceil(8).max(1)
+            }
+            CodeType::FormattedNumber(format, value) => {
+                //Seven bits of the value in each byte
+                let bits = 64 - value.leading_zeros() as usize;
 
+                2 + (format.zeros != 0) as usize + bits.div_ceil(7).max(1)
+            }
+            CodeType::Cased(_, code) => 2 + code.serialized_len(),
+            CodeType::UnicodeWindow(_, run) => 4 + run.len(),
+        }
     }
 
-    pub (crate) fn serialize_into(&self, bytes: & mut Vec<u8>, engine: & Engine) {
+    ///Converts the code into its compressed bytes.
+    ///
+    /// Codes are at most a handful of bytes long, so they are built on the stack rather than allocating.
+    /// The exception is unicode window runs, whose run is borrowed from the code
+    pub (crate) fn to_bytes(&self, engine: & Engine) -> CodeBytes<'_> {
+
+        let mut bytes = CodeBytes::new();
 
         if let CodeType::OneByteWonder(ind) = self {
             bytes.push(*ind as u8);
         } else {
             if let CodeType::UnicodeChar(c) = self {
                 bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
-                bytes.extend_from_slice(c.to_string().as_bytes());
+                bytes.extend_from_slice(c.encode_utf8(& mut [0u8; 4]).as_bytes());
+            } else if let CodeType::RawByte(byte) = self {
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+
+                if *byte >= Self::RAW_BYTE {
+                    bytes.push(Self::RAW_BYTE);
+                }
+
+                bytes.push(*byte);
+            } else if let CodeType::FormattedNumber(format, mut value) = self {
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push((format.negative as u8 * Self::NUMBER_NEGATIVE) | (format.style << 1) | (format.decimals << 3) | if format.zeros != 0 { Self::NUMBER_ZEROS } else { 0 });
+
+                if format.zeros != 0 {
+                    bytes.push(format.zeros);
+                }
+
+                while value >= 0x80 {
+                    bytes.push(value as u8 | 0x80);
+                    value >>= 7;
+                }
+
+                bytes.push(value as u8);
+            } else if let CodeType::Hex(uppercase, digits, run) = self {
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push(if *uppercase { Self::HEX_UPPERCASE } else { Self::HEX });
+                bytes.push(*digits);
+
+                bytes.payload = run;
+            } else if let CodeType::Uuid(uppercase, uuid) = self {
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push(if *uppercase { Self::UUID_UPPERCASE } else { Self::UUID });
+
+                bytes.payload = uuid;
+            } else if let CodeType::Ipv4(octets, port) = self {
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push(if port.is_some() { Self::IPV4_PORT } else { Self::IPV4 });
+                bytes.extend_from_slice(octets);
+
+                if let Some(port) = port {
+                    bytes.extend_from_slice(&port.to_le_bytes());
+                }
+            } else if let CodeType::Ipv6(octets, format) = self {
+                let flags = if format.uppercase { Self::IPV6_UPPERCASE } else { 0 } | if format.expanded { Self::IPV6_EXPANDED } else { 0 } | if format.port.is_some() { Self::IPV6_PORT } else { 0 };
+
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push(Self::IPV6);
+                bytes.push(flags);
+
+                if let Some(port) = format.port {
+                    bytes.extend_from_slice(&port.to_le_bytes());
+                }
+
+                //The address doesn't fit alongside the rest of the code
+                bytes.payload = octets;
+            } else if let CodeType::DateTime(date_time) = self {
+                let shape = Self::date_time_shape(date_time);
+                let radices = date_time_radices(shape);
+
+                bytes.push(Self::ONE_BYTE_WONDER_COUNT as u8);
+                bytes.push(Self::DATE_TIME);
+                bytes.push(shape);
+
+                let (month, day) = (date_time.month - 1, date_time.day - 1);
+                let mut fields = [date_time.year as u128, month as u128, day as u128, 0, 0, 0, 0, 0, 0];
+
+                if let Some(time) = &date_time.time {
+                    let (negative, minutes) = match time.zone {
+                        Zone::Offset { negative, minutes, .. } => (negative as u128, minutes as u128),
+                        _ => (0, 0),
+                    };
+
+                    fields[3..].copy_from_slice(&[time.hour as u128, time.minute as u128, time.second.unwrap_or(0) as u128, time.fraction as u128, negative, minutes]);
+                }
+
+                let mut packed = fields.iter().zip(radices).fold(0u128, |packed, (field, radix)| packed * radix + field);
+
+                for _ in 0..packed_length(&radices) {
+                    bytes.push(packed as u8);
+                    packed >>= 8;
+                }
             } else {
 
-                let (n, extra) = match self {
+                let mut extra = CodeBytes::new();
+
+                let n = match self {
                     CodeType::TwoByteCommon(space, index) => {
-                        let n = if *space {Self::TWO_BYTE_COUNT + *index as usize} else {*index as usize};
-                        (n, None)
+                        if *space {Self::TWO_BYTE_COUNT + *index} else {*index}
                     }
                     CodeType::Custom(space, index) => {
                         let n = if *space && engine.custom_spaces {* index + 16} else {*index};
-                        (n + Self::TWO_BYTE_COUNT*2, None)
+                        n + Self::TWO_BYTE_COUNT*2
+                    }
+                    CodeType::Cased(case, code) => {
+                        for part in code.to_bytes(engine).parts() {
+                            extra.extend_from_slice(part);
+                        }
+
+                        let modifier = match case {
+                            Case::Capitalise => Self::CAPITALISE,
+                            Case::Uppercase => Self::UPPERCASE,
+                        };
+
+                        modifier + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT
+                    }
+                    CodeType::UnicodeWindow(window, run) => {
+                        extra.push(*window);
+                        extra.push(run.len() as u8);
+
+                        bytes.payload = run;
+
+                        Self::UNICODE_WINDOW + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT
                     }
                     CodeType::Repetitions(count, repeat) => {
-                        (*count as usize + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT, Some(vec![*repeat as u8]))
+                        extra.push(*repeat as u8);
+                        *count as usize + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT
                     }
                     CodeType::Number(mut num) => {
 
                         let four = num % 4;
 
-                        num = num >> 2;
-
-                        let mut bytes = Vec::new();
+                        num >>= 2;
 
                         while num != 0 {
-                            bytes.push((num % 256) as u8);
-                            num = num >> 8;
+                            extra.push((num % 256) as u8);
+                            num >>= 8;
                         }
 
-                        (four as usize * 8 + (bytes.len()-1) + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT, Some(bytes))
+                        four as usize * 8 + (extra.len-1) + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT
                     }
                     CodeType::Unprintable(ind) => {
-                        (*ind as usize + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT, None)
+                        *ind + Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT
                     }
                     CodeType::ThreeByteUncommon(space, ind) => {
-                        let n = if *space {Self::THREE_BYTE_COUNT + *ind as usize} else {*ind as usize};
+                        let n = if *space {Self::THREE_BYTE_COUNT + *ind} else {*ind};
 
-                        (Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT + Self::NON_PRINTABLE_COUNT + n/256, Some(vec![(n % 256) as u8]))
+                        extra.push((n % 256) as u8);
+                        Self::TWO_BYTE_COUNT*2 + Self::CUSTOM_COUNT + Self::REPETITION_COUNT + Self::NUMBER_COUNT + Self::NON_PRINTABLE_COUNT + n/256
                     }
                     _ => {unreachable!()}
                 };
@@ -138,16 +554,130 @@ impl CodeType {
                 bytes.push((n / 256 + Self::ONE_BYTE_WONDER_COUNT + Self::UNICODE_COUNT) as u8);
                 bytes.push((n % 256) as u8);
 
-                if let Some(mut b) = extra {
-                    bytes.append(& mut b);
-                }
+                bytes.extend_from_slice(&extra.bytes[..extra.len]);
 
 
             }
         }
+
+        bytes
+    }
+}
+
+//The number of values each field of a date and time with the shape can take, in the order they are packed: year, month, day, hour, minute, second, fraction, offset sign and offset minutes.
+//Fields the shape doesn't have can only be 0
+fn date_time_radices(shape: u8) -> [u128; 9] {
+    let time = shape & CodeType::DATE_TIME_TIME != 0;
+    let precision = (shape >> 4) as u32;
+    let offset = (shape >> 2) & 0x03 >= 2;
+
+    let only = |present: bool, radix: u128| if present { radix } else { 1 };
+
+    [10000, 12, 31, only(time, 24), only(time, 60), only(precision >= 1, 61), 10u128.pow(precision.saturating_sub(1)), only(offset, 2), only(offset, 24 * 60)]
+}
+
+//Bytes needed to hold every packed value of fields with these radices
+fn packed_length(radices: & [u128]) -> usize {
+    let max = radices.iter().product::<u128>() - 1;
+
+    (128 - max.leading_zeros() as usize).div_ceil(8)
+}
+
+//Reads a little endian port number
+fn read_port<S: Source>(source: & mut S) -> Result<u16> {
+    Ok(u16::from_le_bytes([source.read_byte()?, source.read_byte()?]))
+}
+
+//Reads an unsigned LEB128 varint, which takes at most 10 bytes for a u64
+fn read_varint<S: Source>(source: & mut S, offset: usize) -> Result<u64> {
+    let mut value = 0u64;
+
+    for i in 0..10 {
+        let byte = source.read_byte()?;
+
+        //The tenth byte only has room for the top bit
+        if i == 9 && byte > 1 {
+            break;
+        }
+
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(Error::InvalidCode { offset })
+}
+
+//Small fixed size buffer used to assemble a single code without allocating, followed by any payload borrowed from the code
+pub (crate) struct CodeBytes<'a> {
+    bytes: [u8; 16],
+    len: usize,
+    payload: & 'a [u8],
+}
+
+impl<'a> CodeBytes<'a> {
+    fn new() -> Self {
+        Self {
+            bytes: [0u8; 16],
+            len: 0,
+            payload: &[],
+        }
+    }
+
+    fn push(& mut self, byte: u8) {
+        self.bytes[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn extend_from_slice(& mut self, slice: & [u8]) {
+        self.bytes[self.len..self.len + slice.len()].copy_from_slice(slice);
+        self.len += slice.len();
+    }
+
+    //The bytes of the code, in order
+    pub (crate) fn parts(&self) -> [& [u8]; 2] {
+        [&self.bytes[..self.len], self.payload]
     }
 }
 
+//Reads bytes from a std::io::Read, counting the bytes read so errors can report where in the stream they happened
+#[cfg(feature = "std")]
+pub (crate) struct IoSource<R> {
+    reader: R,
+    count: usize,
+}
 
+#[cfg(feature = "std")]
+impl<R: Read> IoSource<R> {
+    pub (crate) fn new(reader: R, start: usize) -> Self {
+        Self {
+            reader,
+            count: start,
+        }
+    }
+}
 
+#[cfg(feature = "std")]
+impl<R: Read> Source for IoSource<R> {
+    fn next_byte(& mut self) -> Result<Option<u8>> {
+        let mut byte = [0u8];
+
+        loop {
+            match self.reader.read(& mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.count += 1;
+                    return Ok(Some(byte[0]));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 
+    fn position(&self) -> usize {
+        self.count
+    }
+}
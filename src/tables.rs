@@ -0,0 +1,300 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::bi::Bi;
+use crate::error::{Error, Result};
+use crate::header::fnv1a;
+
+///Number of entries in the one byte wonder list
+pub const ONE_BYTE_LENGTH: usize = 240;
+
+///Maximum number of entries in the two byte common list
+pub const TWO_BYTE_MAX: usize = 1793;
+
+///Maximum number of entries in the three byte uncommon list
+pub const THREE_BYTE_MAX: usize = 16512;
+
+include!("overlaps.rs");
+
+//Version of the binary format written by Tables::to_bytes
+const TABLES_VERSION: u8 = 1;
+
+//A list of entries that can be looked up by index, or by the bytes of the entry
+pub (crate) enum Table {
+    //Generated by the build script
+    Static(& 'static phf::OrderedMap<& 'static [u8], usize>),
+
+    //Loaded at runtime
+    Owned {
+        entries: Vec<String>,
+        map: BTreeMap<Vec<u8>, usize>,
+    },
+}
+
+impl Table {
+
+    //Checks every entry is unique and not empty. Line numbers in errors start at 1
+    fn new(entries: Vec<String>) -> Result<Self> {
+        let mut map = BTreeMap::new();
+
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.is_empty() {
+                return Err(Error::InvalidTableEntry { line: i + 1 });
+            }
+
+            if map.insert(entry.as_bytes().to_vec(), i).is_some() {
+                return Err(Error::DuplicateTableEntry { line: i + 1 });
+            }
+        }
+
+        Ok(Table::Owned { entries, map })
+    }
+
+    pub (crate) fn len(&self) -> usize {
+        match self {
+            Table::Static(map) => map.len(),
+            Table::Owned { entries, .. } => entries.len(),
+        }
+    }
+
+    //Gets the entry at the index, if there is one
+    pub (crate) fn entry(&self, index: usize) -> Option<& str> {
+        match self {
+            Table::Static(map) => map.index(index).map(|(entry, _)| unsafe { core::str::from_utf8_unchecked(entry) }), //This unsafe is justified as the build script checks the lists for invalid utf-8 strings
+            Table::Owned { entries, .. } => entries.get(index).map(String::as_str),
+        }
+    }
+
+    //Gets the index of the entry, if it is in the table
+    pub (crate) fn index_of(&self, entry: & [u8]) -> Option<usize> {
+        match self {
+            Table::Static(map) => map.get(entry).copied(),
+            Table::Owned { map, .. } => map.get(entry).copied(),
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = & str> {
+        (0..self.len()).filter_map(|i| self.entry(i))
+    }
+}
+
+impl Bi for Table {
+    fn get(&self, string: & [u8]) -> Option<usize> {
+        self.index_of(string)
+    }
+}
+
+///The one byte wonder, two byte common and three byte uncommon lists used to compress and decompress.
+///
+/// By default engines use the lists built into the crate (see [Tables::builtin]), but domain specific lists (medical, legal, code identifiers, ...) can be loaded at runtime and passed to [Builder::set_tables](crate::builder::Builder::set_tables).
+/// Lists can be loaded from the same percent-encoded format as the files in the `lists` directory (one entry per line), or from the more compact binary format written by [Tables::to_bytes].
+///
+/// The one byte wonder list must have exactly [ONE_BYTE_LENGTH] entries, and must keep every printable ascii character (and tab, line feed, carriage return and null) at its own position, as these are used to encode characters no other code matches.
+/// The two byte common and three byte uncommon lists can have up to [TWO_BYTE_MAX] and [THREE_BYTE_MAX] entries.
+///
+/// Strings must be decompressed with the same tables they were compressed with. The tables are part of the engine fingerprint, so enable the header (see [Builder::set_header](crate::builder::Builder::set_header)) to catch mistakes.
+pub struct Tables {
+    pub (crate) one_byte: Table,
+    pub (crate) two_byte: Table,
+    pub (crate) three_byte: Table,
+    //Lengths of every entry (longer than one byte) in any list, from longest to shortest
+    pub (crate) lengths: Vec<usize>,
+    pub (crate) fingerprint: u32,
+}
+
+impl Default for Tables {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Tables {
+
+    ///The lists built into the crate (see [LANGUAGE](crate::LANGUAGE))
+    pub fn builtin() -> Self {
+        Self {
+            one_byte: Table::Static(crate::map::OneByteMap::get_map()),
+            two_byte: Table::Static(crate::map::TwoByteMap::get_map()),
+            three_byte: Table::Static(crate::map::ThreeByteMap::get_map()),
+            lengths: crate::map::TOTAL_LENGTHS.to_vec(),
+            fingerprint: crate::map::TABLE_FINGERPRINT,
+        }
+    }
+
+    ///Loads the tables from three percent-encoded lists, with one entry per line.
+    ///
+    /// Returns an error if any entry is invalid, or if a list is the wrong length (see [Tables]).
+    pub fn from_lists(one_byte: & str, two_byte: & str, three_byte: & str) -> Result<Self> {
+        Self::from_entries(parse_list(one_byte)?, parse_list(two_byte)?, parse_list(three_byte)?)
+    }
+
+    ///Replaces the two byte common list with a percent-encoded list, with one entry per line.
+    pub fn set_two_byte(& mut self, list: & str) -> Result<& mut Self> {
+        *self = Self::from_entries(self.one_byte.entries().map(String::from).collect(), parse_list(list)?, self.three_byte.entries().map(String::from).collect())?;
+        Ok(self)
+    }
+
+    ///Replaces the three byte uncommon list with a percent-encoded list, with one entry per line.
+    pub fn set_three_byte(& mut self, list: & str) -> Result<& mut Self> {
+        *self = Self::from_entries(self.one_byte.entries().map(String::from).collect(), self.two_byte.entries().map(String::from).collect(), parse_list(list)?)?;
+        Ok(self)
+    }
+
+    ///Loads tables written by [Tables::to_bytes]
+    pub fn from_bytes(bytes: & [u8]) -> Result<Self> {
+        let (version, mut rest) = bytes.split_first().ok_or(Error::UnexpectedEndOfBytes)?;
+
+        if *version != TABLES_VERSION {
+            return Err(Error::UnsupportedVersion { version: *version });
+        }
+
+        let mut lists = Vec::new();
+
+        for _ in 0..3 {
+            let (count, read) = crate::framed::decode_length(rest, bytes.len() - rest.len())?;
+            rest = &rest[read..];
+
+            let mut entries = Vec::new();
+
+            for line in 1..=count {
+                let (length, read) = crate::framed::decode_length(rest, bytes.len() - rest.len())?;
+                rest = &rest[read..];
+
+                let entry = rest.get(..length).ok_or(Error::UnexpectedEndOfBytes)?;
+                rest = &rest[length..];
+
+                entries.push(String::from(core::str::from_utf8(entry).map_err(|_| Error::InvalidTableEntry { line })?));
+            }
+
+            lists.push(entries);
+        }
+
+        let three_byte = lists.pop().unwrap();
+        let two_byte = lists.pop().unwrap();
+        let one_byte = lists.pop().unwrap();
+
+        Self::from_entries(one_byte, two_byte, three_byte)
+    }
+
+    ///Writes the tables in a compact binary format, which can be loaded with [Tables::from_bytes]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from([TABLES_VERSION]);
+
+        for table in [&self.one_byte, &self.two_byte, &self.three_byte] {
+            bytes.extend(crate::framed::encode_length(table.len()));
+
+            for entry in table.entries() {
+                bytes.extend(crate::framed::encode_length(entry.len()));
+                bytes.extend_from_slice(entry.as_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    ///Returns the fingerprint of the tables. Tables with the same entries in the same order have the same fingerprint.
+    pub fn fingerprint(&self) -> u32 {
+        self.fingerprint
+    }
+
+    fn from_entries(one_byte: Vec<String>, two_byte: Vec<String>, three_byte: Vec<String>) -> Result<Self> {
+
+        if one_byte.len() != ONE_BYTE_LENGTH {
+            return Err(Error::InvalidTableLength { max: ONE_BYTE_LENGTH, found: one_byte.len() });
+        }
+
+        if two_byte.len() > TWO_BYTE_MAX {
+            return Err(Error::InvalidTableLength { max: TWO_BYTE_MAX, found: two_byte.len() });
+        }
+
+        if three_byte.len() > THREE_BYTE_MAX {
+            return Err(Error::InvalidTableLength { max: THREE_BYTE_MAX, found: three_byte.len() });
+        }
+
+        //Characters that don't match any other code are stored as the one byte wonder at their own position, so these must be left alone
+        for (index, entry) in one_byte.iter().enumerate().take(128) {
+            let control = crate::map::Controls::get_map().get(& [index as u8]).is_some();
+
+            if !control && entry.as_bytes() != [index as u8] {
+                return Err(Error::MissingAsciiEntry { index });
+            }
+        }
+
+        //This must match the fingerprint calculated by the build script
+        let mut fingerprint = 0x811c9dc5u32;
+
+        fingerprint = hash_list(fingerprint, two_byte.iter().map(String::as_str));
+        fingerprint = hash_list(fingerprint, three_byte.iter().map(String::as_str));
+        fingerprint = hash_list(fingerprint, one_byte.iter().map(String::as_str));
+        fingerprint = hash_list(fingerprint, (0..crate::map::Controls::get_length()).map(crate::map::Controls::get_index));
+        fingerprint = hash_list(fingerprint, (0..crate::map::Repetitions::get_length()).map(crate::map::Repetitions::get_index));
+
+        //Three byte entries are only tried after two byte entries of the same length, so one in both lists would never be used
+        let two_byte_entries: BTreeSet<& str> = two_byte.iter().map(String::as_str).collect();
+
+        let unused = three_byte.iter().enumerate().find(|(i, entry)| two_byte_entries.contains(entry.as_str()) && !KNOWN_OVERLAPS.contains(&(entry.as_str(), i + 1)));
+
+        if let Some((i, _)) = unused {
+            return Err(Error::UnusedTableEntry { line: i + 1 });
+        }
+
+        //Sequences of length one are only matched by the one byte wonder fallback, as in the build script
+        let mut lengths: BTreeSet<usize> = one_byte.iter().chain(&two_byte).chain(&three_byte).map(String::len).chain(crate::map::REPETITION_LENGTHS).collect();
+        lengths.remove(&1);
+
+        Ok(Self {
+            one_byte: Table::new(one_byte)?,
+            two_byte: Table::new(two_byte)?,
+            three_byte: Table::new(three_byte)?,
+            lengths: lengths.into_iter().rev().collect(),
+            fingerprint,
+        })
+    }
+}
+
+//Adds every entry of the list to the fingerprint, in the same way as the build script
+fn hash_list<'a, I: Iterator<Item = & 'a str>>(mut fingerprint: u32, list: I) -> u32 {
+    for entry in list {
+        //Each entry is followed by 0xff, which can't appear in utf-8, so the fingerprint changes if characters move from one entry to the next
+        fingerprint = fnv1a(fingerprint, entry.as_bytes());
+        fingerprint = fnv1a(fingerprint, &[0xff]);
+    }
+
+    //Mark the end of the list, so the fingerprint changes if entries are moved between lists
+    fnv1a(fingerprint, &[0xfe])
+}
+
+//Parses a list with one percent-encoded entry per line. Line numbers in errors start at 1
+fn parse_list(list: & str) -> Result<Vec<String>> {
+    list.lines().enumerate().map(|(i, line)| percent_decode(line).ok_or(Error::InvalidTableEntry { line: i + 1 })).collect()
+}
+
+//Decodes %XX escapes, returning None if the result is not valid utf-8.
+//Like the percent-encoding crate used by the build script, a % that isn't followed by two hex digits is kept as is
+fn percent_decode(line: & str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(line.len());
+    let mut rest = line.as_bytes();
+
+    while let Some((first, tail)) = rest.split_first() {
+        match (*first, tail) {
+            (b'%', [high, low, ..]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                bytes.push(hex_value(*high) << 4 | hex_value(*low));
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(*first);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
@@ -1,8 +1,16 @@
+//Tests that check exact compressed sizes or codes depend on the English lists, so only run in English builds. The rest run for every language
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::builder::Builder;
+    use crate::builder::{Builder, ParseStrategy};
     use crate::engine::Engine;
     use crate::iterator::CodeIterator;
+    use crate::error::Error;
+    use crate::train::Trainer;
+    #[cfg(byte_size_language = "en")]
+    use crate::explain::TokenKind;
+    use crate::tables::Tables;
+    use crate::compressed::CompressedStr;
 
     fn full_ser_deser_builder(string: &str, engine: & Engine, compressed_size: usize) {
         use smaz::compress;
@@ -20,18 +28,21 @@ mod tests {
         println!("String: '{}' ({:?})", string, string);
         println!("    Original Size:         {}", string.len());
         println!("    Compression:           {:?}", bytes);
-        println!("    Code Points:           {:?}", CodeIterator::new(string, &engine).collect::<Vec<_>>());
-        println!("    Compression size:      {} ({}% compression ratio)", code_len, 100f32 - code_len as f32 / string.as_bytes().len() as f32 * 100f32);
-        println!("    Smaz Compression size: {} ({}% compression ratio)", smaz_len, 100f32 - smaz_len as f32 / string.as_bytes().len() as f32 * 100f32);
+        println!("    Code Points:           {:?}", CodeIterator::new(string.as_bytes(), true, engine).collect::<Vec<_>>());
+        println!("    Compression size:      {} ({}% compression ratio)", code_len, 100f32 - code_len as f32 / string.len() as f32 * 100f32);
+        println!("    Smaz Compression size: {} ({}% compression ratio)", smaz_len, 100f32 - smaz_len as f32 / string.len() as f32 * 100f32);
 
         //First make sure that the decompression worked correctly
         assert_eq!(string, x.as_str());
 
-        //Next we make sure the size hasn't changed. This means if we change the algorithm, we can catch any changes
-        assert_eq!(code_len, compressed_size);
+        //The sizes below are for the English lists, other languages only check the round trip
+        if cfg!(byte_size_language = "en") {
+            //Next we make sure the size hasn't changed. This means if we change the algorithm, we can catch any changes
+            assert_eq!(code_len, compressed_size);
 
-        //Finally we see if the compressed size is smaller than smaz
-        assert!(code_len <= smaz_len);
+            //Finally we see if the compressed size is smaller than smaz
+            assert!(code_len <= smaz_len);
+        }
 
     }
 
@@ -71,6 +82,92 @@ mod tests {
         full_ser_deser("1000 ", 4);
     }
 
+    #[test]
+    fn number_test5() {
+        //2^66 no longer fits in a number code
+        full_ser_deser("73786976294838206464", 11);
+        full_ser_deser("73786976294838206463", 10);
+    }
+
+    #[test]
+    fn test_formatted_numbers() {
+        full_ser_deser("19.99", 4);
+        full_ser_deser("-1234.56", 5);
+        full_ser_deser("0000000451", 5);
+        full_ser_deser("1,250,000", 5);
+        full_ser_deser("1.250.000,50", 6);
+        full_ser_deser("3.14159", 5);
+        full_ser_deser("0.0005", 3);
+        full_ser_deser("Price: $1,299.99 (was -12.5%)", 20);
+
+        //Short numbers are still smaller than their text
+        for string in ["19.99", "000451", "$19.99", "-12345"] {
+            assert!(crate::engine::compress(string).len() < string.len(), "{}", string);
+        }
+
+        //Separators that aren't every three digits are left as they are
+        full_ser_deser("12,34,567", 8);
+    }
+
+    #[test]
+    fn test_hex() {
+        full_ser_deser("123e4567-e89b-12d3-a456-426614174000", 18);
+        full_ser_deser("123E4567-E89B-12D3-A456-426614174000", 18);
+        full_ser_deser("da39a3ee5e6b4b0d3255bfef95601890afd80709", 23);
+        full_ser_deser("0xDEADBEEF", 9);
+
+        //Mixed case isn't packed, and words made of hex letters stay words
+        full_ser_deser("123e4567-E89B-12d3-a456-426614174000", 30);
+        full_ser_deser("decade feedback facade", 10);
+    }
+
+    #[test]
+    fn test_date_time() {
+        full_ser_deser("2026-10-18", 6);
+        full_ser_deser("2026-10-18T08:38:00Z", 8);
+        full_ser_deser("2026-10-18 08:38", 8);
+        full_ser_deser("1999-12-31T23:59:59.999Z", 10);
+        full_ser_deser("2026-10-18T08:38:00.123456+05:30", 12);
+        full_ser_deser("2026-10-18T08:38:60-00:00", 10);
+        full_ser_deser("[2026-10-18 08:38:00,123] INFO started", 20);
+
+        //Fields out of range are left as they are
+        full_ser_deser("2026-13-01", 10);
+        full_ser_deser("2026-10-18T25:00", 12);
+    }
+
+    #[test]
+    fn test_ip_address() {
+        full_ser_deser("192.168.100.254", 6);
+        full_ser_deser("10.0.0.1:8080", 8);
+        full_ser_deser("GET 192.168.100.254:443 at 08:38:00", 22);
+        full_ser_deser("2001:db8:85a3::8a2e:370:7334", 19);
+        full_ser_deser("2001:0db8:85a3:0000:0000:8a2e:0370:7334", 19);
+        full_ser_deser("2001:DB8:85A3::8A2E:370:7334", 19);
+        full_ser_deser("[2001:db8:85a3::8a2e:370:7334]:443", 21);
+        full_ser_deser("fe80::1ff:fe23:4567:890a: up", 22);
+
+        //Leading zeros and forms that aren't canonical are left as they are
+        full_ser_deser("192.168.001.1", 8);
+        full_ser_deser("2001:db8:0:0:1:0:0:1", 20);
+
+        assert_eq!(optimal().compress("::ffff:192.168.100.254").len(), 12);
+    }
+
+    #[test]
+    fn test_long_address_runs() {
+        //Runs far longer than any address shouldn't be scanned in full at every position
+        let colons = "ab:".repeat(5_000);
+        let dots = "1.".repeat(5_000);
+        let hex = "2001:db8:".repeat(2_000) + "::1";
+
+        for engine in [Builder::default().engine(), optimal()] {
+            for string in [&colons, &dots, &hex] {
+                assert_eq!(engine.decompress(&engine.compress(string)).unwrap(), *string);
+            }
+        }
+    }
+
     #[test]
     fn http1() {
         full_ser_deser("http://google.com", 6);
@@ -220,6 +317,438 @@ small strings will not work.", 127);
         full_ser_deser_builder("customstringspacetest", &Builder::default().set_custom_spaces(false).push_custom("customstringspacetest").engine(), 2)
     }
 
+    #[test]
+    fn capitalised_test() {
+        full_ser_deser("Information", 4);
+    }
+
+    #[test]
+    fn uppercase_test() {
+        full_ser_deser("SEARCH RESULTS", 8);
+    }
+
+    #[test]
+    fn capitalised_sentence_test() {
+        full_ser_deser("Searching The Internet For Information", 18);
+    }
+
+    #[test]
+    fn capitalised_custom_test() {
+        let engine = Builder::empty().set_custom(["customstring"]).set_custom_spaces(true).engine();
+
+        full_ser_deser_builder("Customstring CUSTOMSTRING", &engine, 8);
+    }
+
+    #[test]
+    fn capitalised_optimal_test() {
+        full_ser_deser_builder("Searching The Internet For Information", &optimal(), 18);
+    }
+
+    #[test]
+    fn window_cyrillic_test() {
+        full_ser_deser("Привет, как дела?", 21);
+    }
+
+    #[test]
+    fn window_greek_test() {
+        full_ser_deser("Καλημέρα κόσμε", 18);
+    }
+
+    #[test]
+    fn window_mixed_test() {
+        full_ser_deser("The word мир means peace and 🙂 means happy", 27);
+    }
+
+    #[test]
+    fn window_optimal_test() {
+        full_ser_deser_builder("Привет, как дела? Grüße aus München", &optimal(), 41);
+    }
+
+    fn optimal() -> Engine {
+        Builder::default().set_parse_strategy(ParseStrategy::Optimal).engine()
+    }
+
+    #[test]
+    fn optimal_test() {
+        full_ser_deser_builder("antidisestablishmentarianism", &optimal(), 12);
+    }
+
+    #[test]
+    fn test_optimal_numbers() {
+        let engine = optimal();
+
+        //Splitting a number too long for one number code into two can be cheaper than taking the longest first
+        full_ser_deser_builder("123456789012345678901234567890", &engine, 16);
+        full_ser_deser_builder("12345678901234567890123456", &engine, 14);
+
+        for string in ["123456789012345678901234567890", "12345678901234567890123456", "000451000451", "-1234.567890123456789012", "1,250,000,000,000,000,000,000", "2026-10-18T08:38:00.1234567899", "deadbeef0123456789abcdef01234567"] {
+            //The smallest encoding out of every way of splitting the string, with each part compressed on its own
+            let mut best = vec![0; string.len() + 1];
+
+            for start in (0..string.len()).rev() {
+                best[start] = (start + 1..=string.len()).map(|end| engine.compress(&string[start..end]).len() + best[end]).min().unwrap();
+            }
+
+            assert_eq!(engine.compress(string).len(), best[0], "{}", string);
+        }
+    }
+
+    #[test]
+    fn optimal_test1() {
+        full_ser_deser_builder("oohe he he he he he ", &optimal(), 5);
+    }
+
+    #[test]
+    fn optimal_test2() {
+        full_ser_deser_builder("this is an example of what works very well with smaz", &optimal(), 22);
+    }
+
+    #[test]
+    fn optimal_never_worse() {
+        let greedy = Builder::default().engine();
+        let optimal = optimal();
+
+        for string in ["The quick brown fox jumped over the lazy dog", "not-a-g00d-Exampl333", "1000 numbers 2000 will 10 20 30 compress very little", "hhhhhhhhhhhhhhohhhhhhhhhhh", "yeh thats fine mate 🙂", "✔️ ❤️ ☆", "http://programming.reddit.com", "\x01\r\n\x7f"] {
+            let bytes = optimal.compress(string);
+
+            assert_eq!(optimal.decompress(bytes.as_slice()).unwrap(), string);
+            assert!(bytes.len() <= greedy.compress(string).len());
+        }
+    }
+
+    #[test]
+    fn long_repetition() {
+        full_ser_deser(&"h".repeat(40), 6);
+    }
+
+    #[test]
+    fn test_owned_custom() {
+        //Simulate a list loaded at runtime, such as from a config file
+        let config = String::from("\nexample.org\n\nbyte-size\n");
+
+        let engine = Builder::empty().set_custom(config.lines().map(String::from)).push_custom(String::from("customstring")).engine();
+
+        drop(config);
+
+        full_ser_deser_builder("https://example.org/byte-size", &engine, 11);
+        full_ser_deser_builder("customstring", &engine, 2);
+    }
+
+    #[test]
+    fn test_empty_custom() {
+        //An empty string keeps its index, so the strings after it keep their codes
+        let engine = Builder::empty().set_custom(["", "foobar"]).engine();
+        let bytes = engine.compress("foobar");
+
+        assert_eq!(bytes, Builder::empty().set_custom(["unused", "foobar"]).engine().compress("foobar"));
+        assert_eq!(engine.decompress(&bytes).unwrap(), "foobar");
+        assert!(matches!(engine.decompress(&[255, 2]), Err(Error::InvalidCustomIndex { index: 0, offset: 0 })));
+    }
+
+    #[test]
+    fn test_extend_custom() {
+        full_ser_deser_builder("http://github.com/antirez/smaz/tree/master", &Builder::default().extend_custom(vec![String::from("http://github.com/"), String::from("antirez")]).engine(), 14);
+    }
+
+    #[test]
+    fn test_compress_to_vec() {
+        let engine = Builder::default().engine();
+        let string = "The quick brown fox jumped over the lazy dog";
+
+        let mut bytes = Vec::with_capacity(64);
+        bytes.push(42);
+
+        let written = engine.compress_to_vec(string, & mut bytes);
+
+        assert_eq!(written, engine.compress(string).len());
+        assert_eq!(bytes[0], 42);
+        assert_eq!(&bytes[1..], engine.compress(string).as_slice());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_compress_into() {
+        let engine = Builder::default().engine();
+        let string = "http://programming.reddit.com 1000 numbers 🙂";
+
+        let mut buffer = [0u8; 64];
+        let mut cursor = std::io::Cursor::new(&mut buffer[..]);
+
+        let written = engine.compress_into(string, & mut cursor).unwrap();
+
+        assert_eq!(engine.decompress(&buffer[..written]).unwrap(), string);
+
+        //Writers that run out of space should return an error rather than panic
+        let mut small = [0u8; 4];
+        assert!(engine.compress_into(string, & mut small.as_mut_slice()).is_err());
+    }
+
+    #[test]
+    fn test_decompress_into() {
+        let engine = Builder::default().engine();
+
+        let mut string = String::from("> ");
+
+        engine.decompress_into(engine.compress("the end").as_slice(), & mut string).unwrap();
+        engine.decompress_into(engine.compress(", foobar 🙂").as_slice(), & mut string).unwrap();
+
+        assert_eq!(string, "> the end, foobar 🙂");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_decompress_from() {
+        use std::fmt::Write;
+
+        let engine = Builder::default().engine();
+        let string = "Smaz is a simple compression library 1000 \x01";
+
+        //Read one byte at a time to make sure codes split across reads are handled
+        let bytes = engine.compress(string);
+        let reader = std::io::BufReader::with_capacity(1, bytes.as_slice());
+
+        let mut body = String::new();
+        write!(body, "body: ").unwrap();
+
+        engine.decompress_from(reader, & mut body).unwrap();
+
+        assert_eq!(body, format!("body: {}", string));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_decompress_from_errors() {
+        let engine = Builder::empty().engine();
+        let mut string = String::new();
+
+        assert!(matches!(engine.decompress_from([97, 255].as_slice(), & mut string), Err(Error::UnexpectedEndOfBytes)));
+        assert!(matches!(engine.decompress_from([97, 97, 255, 2].as_slice(), & mut string), Err(Error::InvalidCustomIndex { index: 0, offset: 2 })));
+    }
+
+    #[test]
+    fn test_framed() {
+        let engine = Builder::default().engine();
+
+        let strings = ["the end", "", "http://google.com", &"Smaz is a simple compression library ".repeat(10), "🙂"];
+
+        let mut bytes = Vec::new();
+
+        for string in strings {
+            engine.compress_framed_to_vec(string, & mut bytes);
+        }
+
+        assert_eq!(engine.framed_reader(bytes.as_slice()).collect::<crate::Result<Vec<_>>>().unwrap(), strings);
+        #[cfg(byte_size_language = "en")]
+        assert_eq!(engine.compress_framed("the end"), [3, 1, 249, 16]);
+    }
+
+    #[test]
+    fn test_framed_errors() {
+        let engine = Builder::empty().engine();
+
+        let mut bytes = engine.compress_framed("the end");
+        let first = bytes.len();
+        bytes.extend_from_slice(&[2, 255, 2]);
+
+        let mut reader = engine.framed_reader(bytes.as_slice());
+
+        //Offsets count from the start of the bytes, and the custom code comes after the second record's length
+        assert_eq!(reader.next().unwrap().unwrap(), "the end");
+        assert!(matches!(reader.next(), Some(Err(Error::InvalidCustomIndex { index: 0, offset })) if offset == first + 1));
+        assert!(reader.next().is_none());
+
+        assert!(matches!(engine.framed_reader([5, 97].as_slice()).next(), Some(Err(Error::UnexpectedEndOfBytes))));
+        assert!(matches!(engine.framed_reader([0x80].as_slice()).next(), Some(Err(Error::UnexpectedEndOfBytes))));
+        assert!(matches!(engine.framed_reader([0xff; 11].as_slice()).next(), Some(Err(Error::InvalidRecordLength { offset: 0 }))));
+    }
+
+    #[test]
+    fn test_header() {
+        let engine = Builder::default().set_header(true).engine();
+
+        let bytes = engine.compress("the end");
+
+        assert_eq!(bytes.len(), 5 + crate::engine::compress("the end").len());
+        assert_eq!(bytes[0], 7);
+        assert_eq!(&bytes[1..5], &engine.fingerprint().to_le_bytes());
+        assert_eq!(engine.decompress(bytes.as_slice()).unwrap(), "the end");
+
+        let mut records = Vec::new();
+        engine.compress_framed_to_vec("foobar", & mut records);
+        engine.compress_framed_to_vec("", & mut records);
+        assert_eq!(engine.framed_reader(records.as_slice()).collect::<crate::Result<Vec<_>>>().unwrap(), ["foobar", ""]);
+    }
+
+    #[test]
+    fn test_header_mismatch() {
+        let bytes = Builder::default().set_header(true).engine().compress("the end");
+
+        let other = Builder::default().push_custom("the end").set_header(true).engine();
+        assert!(matches!(other.decompress(bytes.as_slice()), Err(Error::TableMismatch { .. })));
+
+        let other = Builder::default().set_custom_spaces(true).set_header(true).engine();
+        assert!(matches!(other.decompress(bytes.as_slice()), Err(Error::TableMismatch { .. })));
+
+        let engine = Builder::default().set_header(true).engine();
+        assert!(matches!(engine.decompress(&[8, 0, 0, 0, 0]), Err(Error::UnsupportedVersion { version: 8 })));
+
+        //Version 1 bytes are still accepted
+        let mut old = bytes.clone();
+        old[0] = 1;
+        assert_eq!(engine.decompress(old.as_slice()).unwrap(), "the end");
+        assert!(matches!(engine.decompress(&bytes[..3]), Err(Error::UnexpectedEndOfBytes)));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        //The fingerprint only depends on the tables and options that affect the compressed bytes
+        assert_eq!(Builder::default().engine().fingerprint(), Builder::default().set_parse_strategy(ParseStrategy::Optimal).set_header(true).engine().fingerprint());
+        assert_ne!(Builder::default().engine().fingerprint(), Builder::empty().set_custom(["", "http://", "https://", ".com", "\n\r\n", "\r\n\r", "C:\\", ".co.uk"]).engine().fingerprint());
+        assert_ne!(Builder::default().engine().fingerprint(), Builder::empty().engine().fingerprint());
+        assert_ne!(Builder::empty().push_custom("ab").push_custom("c").engine().fingerprint(), Builder::empty().push_custom("a").push_custom("bc").engine().fingerprint());
+    }
+
+    #[test]
+    fn test_batch() {
+        let engine = Builder::default().engine();
+        let strings = ["The quick brown fox", "", "jumped over", "the lazy dog 12345"];
+
+        let (bytes, offsets) = engine.compress_batch(&strings);
+
+        assert_eq!(offsets.len(), strings.len() + 1);
+        assert_eq!(offsets[0], 0);
+        assert_eq!(offsets[1], offsets[2]);
+        assert_eq!(bytes, strings.iter().flat_map(|string| engine.compress(string)).collect::<Vec<_>>());
+        assert_eq!(&bytes[offsets[2] as usize..offsets[3] as usize], engine.compress("jumped over").as_slice());
+        assert_eq!(engine.decompress_batch(&bytes, &offsets).unwrap(), strings);
+
+        assert!(engine.decompress_batch(&[], &[]).unwrap().is_empty());
+        assert!(engine.decompress_batch(&[], &[0]).unwrap().is_empty());
+
+        //Errors give the position in the whole buffer
+        let mut corrupt = bytes.clone();
+        corrupt[offsets[2] as usize] = 255;
+        corrupt[offsets[2] as usize + 1] = 22;
+        assert!(matches!(engine.decompress_batch(&corrupt, &offsets), Err(Error::InvalidCustomIndex { index: 20, offset }) if offset == offsets[2] as usize));
+
+        assert!(matches!(engine.decompress_batch(&bytes, &[0, 5, 3]), Err(Error::InvalidBatchOffset { index: 2 })));
+        assert!(matches!(engine.decompress_batch(&bytes, &[0, bytes.len() as u32 + 1]), Err(Error::InvalidBatchOffset { index: 1 })));
+
+        //Enough strings to be split between threads with the rayon feature
+        let strings: Vec<String> = (0..5000).map(|i| format!("customer {} paid invoice {}", i, i * 7919)).collect();
+
+        let (bytes, offsets) = engine.compress_batch(&strings);
+
+        assert_eq!(bytes, strings.iter().flat_map(|string| engine.compress(string)).collect::<Vec<_>>());
+        assert_eq!(engine.decompress_batch(&bytes, &offsets).unwrap(), strings);
+    }
+
+    #[test]
+    fn test_default_engine() {
+        fn shareable<T: Send + Sync + Clone>() {}
+        shareable::<Engine>();
+
+        //Every thread gets the same engine, however many race to build it
+        let engines: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8).map(|_| scope.spawn(|| Engine::default_ref() as * const Engine as usize)).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert!(engines.iter().all(|engine| *engine == Engine::default_ref() as * const Engine as usize));
+        assert_eq!(Engine::default_ref().fingerprint(), Builder::default().engine().fingerprint());
+
+        let string = "The quick brown fox jumped over the lazy dog";
+        let engine = Engine::default_ref().clone();
+
+        assert_eq!(engine.compress(string), crate::engine::compress(string));
+        assert_eq!(Builder::default().engine().compress(string), crate::engine::compress(string));
+    }
+
+    fn training_corpus() -> Vec<String> {
+        (0..40).map(|i| format!("https://api.example.org/v2/customers/{}/invoices?status=overdue", i * 7919)).collect()
+    }
+
+    #[test]
+    fn test_train() {
+        let corpus = training_corpus();
+
+        let trainer = Trainer::default();
+        let custom = trainer.select(corpus.iter().map(|s| s.as_str()));
+
+        assert!(!custom.is_empty() && custom.len() <= 32);
+
+        let trained = trainer.train(corpus.iter().map(|s| s.as_str())).engine();
+        let default = Builder::default().engine();
+
+        let trained_size: usize = corpus.iter().map(|s| trained.compress(s).len()).sum();
+        let default_size: usize = corpus.iter().map(|s| default.compress(s).len()).sum();
+
+        assert!(trained_size < default_size / 2);
+
+        for string in &corpus {
+            assert_eq!(trained.decompress(trained.compress(string).as_slice()).unwrap(), *string);
+        }
+    }
+
+    #[test]
+    fn test_train_spaces() {
+        let corpus: Vec<_> = (0..30).map(|i| format!("kubernetes pod restarted {} times by kubelet on node-{}", i, i % 3)).collect();
+
+        let custom = Trainer::default().set_custom_spaces(true).select(corpus.iter().map(|s| s.as_str()));
+
+        assert!(!custom.is_empty() && custom.len() <= 16);
+        assert!(custom.iter().all(|s| !s.starts_with(' ')));
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "en")]
+    fn test_explain() {
+        let engine = Builder::default().engine();
+        let string = "http://google.com 12345 hehehehe\x01🙂 the end";
+
+        let tokens = engine.explain(string);
+
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TokenKind::Custom, TokenKind::TwoByteCommon, TokenKind::Custom, TokenKind::OneByteWonder, TokenKind::Number, TokenKind::OneByteWonder, TokenKind::Repetition, TokenKind::Control, TokenKind::Unicode, TokenKind::OneByteWonder, TokenKind::OneByteWonder, TokenKind::TwoByteCommon]);
+
+        assert_eq!(tokens[0].entry.as_deref(), Some("http://"));
+        assert_eq!(tokens[4].entry, None);
+        assert_eq!(&string[tokens[4].span.clone()], "12345");
+        assert!(tokens[11].space);
+        assert_eq!(tokens[11].entry.as_deref(), Some("end"));
+
+        //The tokens cover the whole string, and their bytes make up the compressed bytes
+        assert_eq!(tokens.first().unwrap().span.start, 0);
+        assert_eq!(tokens.last().unwrap().span.end, string.len());
+        assert!(tokens.windows(2).all(|pair| pair[0].span.end == pair[1].span.start));
+        assert_eq!(tokens.iter().flat_map(|t| t.bytes.clone()).collect::<Vec<_>>(), engine.compress(string));
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "en")]
+    fn test_explain_case() {
+        let engine = Builder::default().engine();
+
+        let tokens = engine.explain("Information SEARCH");
+
+        assert_eq!(tokens[0].kind, TokenKind::Capitalised);
+        assert_eq!(tokens[0].entry.as_deref(), Some("information"));
+        assert_eq!(tokens[1].kind, TokenKind::Uppercase);
+        assert_eq!(tokens[1].entry.as_deref(), Some("search"));
+        assert!(tokens[1].space);
+    }
+
+    #[test]
+    fn test_explain_optimal() {
+        let engine = optimal();
+        let string = "antidisestablishmentarianism";
+
+        let tokens = engine.explain(string);
+
+        assert_eq!(tokens.iter().map(|t| t.bytes.len()).sum::<usize>(), engine.compress(string).len());
+        assert_eq!(tokens.iter().map(|t| &string[t.span.clone()]).collect::<String>(), string);
+    }
+
     #[test]
     #[should_panic]
     fn test_bad_double() {
@@ -227,9 +756,293 @@ small strings will not work.", 127);
     }
 
     #[test]
-    #[should_panic]
     fn test_bad_unicode() {
-        crate::engine::decompress([240, 0x80, 0x81].as_slice()).unwrap();
+        //Bytes after the unicode marker that start no code and can't start a scalar value
+        assert!(matches!(crate::engine::decompress([240, 0xc1, 0x81].as_slice()), Err(Error::InvalidUnicodeChar)));
+        assert!(matches!(crate::engine::decompress([240, 0xfd].as_slice()), Err(Error::InvalidUnicodeChar)));
+    }
+
+    #[test]
+    fn test_bad_custom() {
+        assert!(matches!(Builder::empty().engine().decompress([97, 255, 2].as_slice()), Err(Error::InvalidCustomIndex { index: 0, offset: 1 })));
+    }
+
+    #[test]
+    fn test_bad_repetition() {
+        assert!(matches!(crate::engine::decompress([255, 38, 200].as_slice()), Err(Error::InvalidRepetitionIndex { index: 200, offset: 0 })));
+    }
+
+    #[test]
+    fn test_bad_case() {
+        //Case modifiers must be followed by a word, not a one byte wonder
+        assert!(matches!(crate::engine::decompress([255, 34, 97].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([255, 35].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+
+        //Nor by another case modifier, however many of them there are
+        assert!(matches!(crate::engine::decompress([255, 34, 255, 35, 244, 0].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([255, 34].repeat(100_000).as_slice()), Err(Error::InvalidCode { offset: 0 })));
+    }
+
+    #[test]
+    fn test_compress_bytes() {
+        let engine = Builder::default().engine();
+
+        //Valid utf-8 is compressed the same either way
+        let string = "The quick brown fox jumped over the lazy dog Привет 12345";
+        assert_eq!(engine.compress_bytes(string.as_bytes()), engine.compress(string));
+        assert_eq!(engine.decompress_bytes(&engine.compress(string)).unwrap(), string.as_bytes());
+
+        //Continuation bytes take two bytes, other bytes three
+        assert_eq!(engine.compress_bytes(b"\x80"), [240, 0x80]);
+        assert_eq!(engine.compress_bytes(b"\xff"), [240, 0xc0, 0xff]);
+        assert_eq!(engine.compress_bytes(b"\xc3("), [240, 0xc0, 0xc3, b'(']);
+
+        //Latin-1
+        let latin = b"caf\xe9 na\xefve, \xa3100";
+        let bytes = engine.compress_bytes(latin);
+
+        #[cfg(byte_size_language = "en")]
+        assert_eq!(bytes.len(), 18);
+        assert_eq!(engine.decompress_bytes(&bytes).unwrap(), latin);
+        assert!(matches!(engine.decompress(&bytes), Err(Error::InvalidUtf8 { offset: 3 })));
+
+        let optimal = optimal();
+        assert_eq!(optimal.decompress_bytes(&optimal.compress_bytes(latin)).unwrap(), latin);
+
+        let mut state = 0x2545f4914f6cdd1du64;
+
+        for _ in 0..1000 {
+            let mut random = Vec::new();
+
+            for _ in 0..(state % 24) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                random.push(state as u8);
+            }
+
+            assert_eq!(engine.decompress_bytes(&engine.compress_bytes(&random)).unwrap(), random);
+            assert_eq!(optimal.decompress_bytes(&optimal.compress_bytes(&random)).unwrap(), random);
+        }
+    }
+
+    #[test]
+    fn test_bad_raw_byte() {
+        //Bytes from 0xC0 up must follow the marker, continuation bytes have their own code
+        assert!(matches!(Engine::default_ref().decompress_bytes(&[240, 0xc0, 0x41]), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(Engine::default_ref().decompress_bytes(&[240, 0xc0]), Err(Error::UnexpectedEndOfBytes)));
+        assert!(matches!(Engine::default_ref().decompress_bytes(&[240, 0xc3]), Err(Error::UnexpectedEndOfBytes)));
+        assert!(matches!(Engine::default_ref().decompress_bytes(&[240, 0xc1, 0x81]), Err(Error::InvalidUnicodeChar)));
+        assert!(matches!(crate::engine::decompress(&[b'a', 240, 0x80]), Err(Error::InvalidUtf8 { offset: 1 })));
+    }
+
+    #[test]
+    fn test_bad_formatted_number() {
+        assert_eq!(crate::engine::decompress([240, 0x51, 2, 0xcf, 0x0f].as_slice()).unwrap(), "-0019.99");
+
+        //Zero counts can't be zero, and varints can't overflow
+        assert!(matches!(crate::engine::decompress([240, 0x40, 0, 1].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0, 0x80].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+    }
+
+    #[test]
+    fn test_bad_hex() {
+        assert_eq!(crate::engine::decompress([240, 0xf8, 3, 0xab, 0xc0].as_slice()).unwrap(), "ABC");
+
+        //Runs can't be empty, and the unused half of an odd run must be zero
+        assert!(matches!(crate::engine::decompress([240, 0xf7, 0].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xf7, 3, 0xab, 0xcd].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xf5, 0x12].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+    }
+
+    #[test]
+    fn test_bad_date_time() {
+        assert_eq!(crate::engine::decompress([240, 0xf9, 0, 48, 129, 11].as_slice()).unwrap(), "2026-10-18");
+
+        //Dates on their own can't have a shape, there are at most nine digits after the seconds, and the packed fields must be in range
+        assert!(matches!(crate::engine::decompress([240, 0xf9, 0x02, 48, 129, 11].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xf9, 0xb1].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xf9, 0, 0xff, 0xff, 0xff].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xf9, 0, 48].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+    }
+
+    #[test]
+    fn test_bad_ip_address() {
+        assert_eq!(crate::engine::decompress([240, 0xfb, 10, 0, 0, 1, 0x90, 0x1f].as_slice()).unwrap(), "10.0.0.1:8080");
+        assert_eq!(crate::engine::decompress([[240, 0xfc, 0x05, 0xbb, 0x01].as_slice(), &[0; 15], &[1]].concat().as_slice()).unwrap(), "[::1]:443");
+
+        assert!(matches!(crate::engine::decompress([240, 0xfc, 0x08].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([240, 0xfa, 10, 0, 0].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+    }
+
+    #[test]
+    fn test_bad_window() {
+        //Window runs can't be empty
+        assert!(matches!(crate::engine::decompress([255, 36, 8, 0].as_slice()), Err(Error::InvalidCode { offset: 0 })));
+        assert!(matches!(crate::engine::decompress([255, 36, 8, 2, 0x3f].as_slice()), Err(Error::UnexpectedEndOfBytes)));
+        assert_eq!(crate::engine::decompress([255, 36, 8, 2, 0x3f, 0xa0].as_slice()).unwrap(), "п ");
+    }
+
+    fn lists() -> (& 'static str, & 'static str, & 'static str) {
+        (include_str!("../lists/obw.txt"), include_str!("../lists/tbc.txt"), include_str!("../lists/tbu.txt"))
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "en")]
+    fn test_tables_from_lists() {
+        let (obw, tbc, tbu) = lists();
+
+        let tables = Tables::from_lists(obw, tbc, tbu).unwrap();
+
+        //The same lists as the built in tables give the same fingerprint and the same bytes
+        assert_eq!(tables.fingerprint(), Tables::builtin().fingerprint());
+
+        let engine = Builder::default().set_tables(tables).engine();
+        let string = "The quick brown fox jumped over the lazy dog 12345";
+
+        assert_eq!(engine.compress(string), crate::engine::compress(string));
+        assert_eq!(engine.fingerprint(), Builder::default().engine().fingerprint());
+    }
+
+    #[test]
+    fn test_tables_bytes() {
+        let bytes = Tables::builtin().to_bytes();
+
+        let tables = Tables::from_bytes(bytes.as_slice()).unwrap();
+
+        assert_eq!(tables.fingerprint(), Tables::builtin().fingerprint());
+        assert_eq!(tables.to_bytes(), bytes);
+
+        assert!(matches!(Tables::from_bytes(&bytes[..bytes.len() - 1]), Err(Error::UnexpectedEndOfBytes)));
+        assert!(matches!(Tables::from_bytes(&[2]), Err(Error::UnsupportedVersion { version: 2 })));
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "en")]
+    fn test_domain_tables() {
+        let mut tables = Tables::builtin();
+
+        //Myocardial is already a three byte entry
+        assert!(matches!(tables.set_two_byte("myocardial\ninfarction"), Err(Error::UnusedTableEntry { line: 14429 })));
+
+        tables.set_two_byte("infarction\ntachycardia\nechocardiogram").unwrap();
+
+        let engine = Builder::default().set_tables(tables).set_header(true).engine();
+        let string = "echocardiogram shows tachycardia after myocardial infarction";
+
+        let bytes = engine.compress(string);
+
+        assert_eq!(bytes.len(), 5 + 18);
+        assert_eq!(engine.decompress(bytes.as_slice()).unwrap(), string);
+        assert_ne!(engine.fingerprint(), Builder::default().set_header(true).engine().fingerprint());
+        assert!(matches!(Builder::default().set_header(true).engine().decompress(bytes.as_slice()), Err(Error::TableMismatch { .. })));
+
+        //Two byte codes past the end of the shorter list are invalid
+        let engine = Builder::default().set_tables(Tables::from_bytes(engine.tables().to_bytes().as_slice()).unwrap()).engine();
+        assert!(matches!(engine.decompress(&[241, 4]), Err(Error::InvalidCode { offset: 0 })));
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        let (obw, tbc, tbu) = lists();
+
+        assert!(matches!(Tables::from_lists(obw, "one\ntwo\none", tbu), Err(Error::DuplicateTableEntry { line: 3 })));
+        assert!(matches!(Tables::from_lists(obw, "one\n\ntwo", tbu), Err(Error::InvalidTableEntry { line: 2 })));
+        assert!(matches!(Tables::from_lists(obw, "one\n%FF", tbu), Err(Error::InvalidTableEntry { line: 2 })));
+        assert!(matches!(Tables::from_lists(obw, &format!("{}extra", tbc), tbu), Err(Error::InvalidTableLength { max: 1793, found: 1794 })));
+        assert!(matches!(Tables::from_lists(&obw[..obw.len() - 4], tbc, tbu), Err(Error::InvalidTableLength { max: 240, found: 239 })));
+        assert!(matches!(Tables::from_lists(&obw.replacen("\nA\n", "\nB\n", 1), tbc, tbu), Err(Error::MissingAsciiEntry { index: 65 })));
+        assert!(matches!(Tables::from_lists(obw, "one\ntwo", "three\ntwo"), Err(Error::UnusedTableEntry { line: 2 })));
+
+        //The known overlaps at the start of the English three byte list are allowed, but only there
+        assert!(Tables::from_lists(obw, tbc, tbu).is_ok());
+        assert!(Tables::from_lists(obw, "their", "their").is_ok());
+        assert!(matches!(Tables::from_lists(obw, "their", "forum\ntheir"), Err(Error::UnusedTableEntry { line: 2 })));
+    }
+
+    #[test]
+    fn test_compressed_str() {
+        use std::collections::HashSet;
+
+        let string = "The quick brown fox jumped over the lazy dog";
+        let compressed = CompressedStr::from(string);
+
+        assert_eq!(compressed.as_bytes(), crate::engine::compress(string).as_slice());
+        assert_eq!(compressed.to_string(), string);
+        assert_eq!(compressed.decompress(), string);
+        assert_eq!(format!("{:?}", compressed), format!("CompressedStr({:?})", string));
+        assert_eq!(String::from(compressed.clone()), string);
+        assert_eq!(CompressedStr::default().to_string(), "");
+
+        //Width, fill, alignment and precision are applied like they are for a str
+        let short = CompressedStr::from("fox");
+        assert_eq!(format!("{:>6}|{:-<5}|{:^7}|{:.2}", short, short, short, short), "   fox|fox--|  fox  |fo");
+
+        let set: HashSet<_> = ["one", "two", "one", "three"].into_iter().map(CompressedStr::from).collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&CompressedStr::from(String::from("two"))));
+
+        assert_eq!(CompressedStr::from_bytes(compressed.as_bytes()).unwrap(), compressed);
+        assert!(matches!(CompressedStr::from_bytes(&[241]), Err(Error::UnexpectedEndOfBytes)));
+
+        //Bytes that decompress to the same string are compressed again, so they are still equal
+        assert_ne!(CompressedStr::from("The").as_bytes(), b"The");
+
+        let spelled_out = CompressedStr::from_bytes(b"The").unwrap();
+        assert_eq!(spelled_out, CompressedStr::from("The"));
+        assert_eq!(spelled_out.as_bytes(), CompressedStr::from("The").as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_compressed_str_serde() {
+        use serde_test::{Configure, Token, assert_tokens, assert_de_tokens, assert_de_tokens_error};
+
+        let compressed = CompressedStr::from("the quick brown fox");
+        let bytes: & 'static [u8] = compressed.as_bytes().to_vec().leak();
+
+        assert_tokens(&compressed.clone().readable(), &[Token::Str("the quick brown fox")]);
+        assert_tokens(&compressed.clone().compact(), &[Token::Bytes(bytes)]);
+
+        //Formats that store bytes as a list of numbers
+        let mut tokens = vec![Token::Seq { len: Some(bytes.len()) }];
+        tokens.extend(bytes.iter().map(|byte| Token::U8(*byte)));
+        tokens.push(Token::SeqEnd);
+        assert_de_tokens(&compressed.clone().compact(), &tokens);
+
+        assert_de_tokens_error::<serde_test::Compact<CompressedStr>>(&[Token::Bytes(&[241])], "Unexpected end of bytes. Deserialiser expected more bytes in the decompress slice");
+    }
+
+    #[test]
+    fn test_no_panic() {
+        let engine = Builder::empty().engine();
+
+        //Every code is at most three bytes long (plus payload), so every one and two byte prefix, followed by a few trailing bytes, covers every code type
+        for first in 0..=255u8 {
+            for second in 0..=255u8 {
+                for third in [0u8, 1, 116, 117, 128, 255] {
+                    let _ = engine.decompress([first, second, third].as_slice());
+                    let _ = engine.decompress([first, second, third, 0xff, 0x80].as_slice());
+                }
+            }
+        }
+
+        //Then throw some pseudo random data at the decompressor
+        let mut state = 0x2545f4914f6cdd1du64;
+
+        for _ in 0..10000 {
+            let mut bytes = Vec::new();
+
+            for _ in 0..(state % 24) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                bytes.push(state as u8);
+            }
+
+            let _ = engine.decompress(bytes.as_slice());
+        }
     }
 
     #[test]
@@ -239,12 +1052,12 @@ small strings will not work.", 127);
         let str = std::fs::read_to_string(".\\.3m.txt").unwrap();
 
         for line in str.lines().take(10000) {
-            let line = line.split_whitespace().nth(0).unwrap();
+            let line = line.split_whitespace().next().unwrap();
 
             let code_len = crate::engine::compress(line).len();
 
             if code_len > 3 {
-                println!("{}: {:?}", line, CodeIterator::new(line, &Builder::default().engine()).collect::<Vec<_>>());
+                println!("{}: {:?}", line, CodeIterator::new(line.as_bytes(), true, &Builder::default().engine()).collect::<Vec<_>>());
                 count += 1;
             }
 
@@ -266,7 +1079,7 @@ small strings will not work.", 127);
 
 
         for line in str.lines() {
-            let line = line.split_whitespace().nth(0).unwrap();
+            let line = line.split_whitespace().next().unwrap();
 
             let code_len = crate::engine::compress(line).len();
 
@@ -288,3 +1101,68 @@ small strings will not work.", 127);
 
 
 }
+
+//Tests that hold for every language pack
+#[cfg(test)]
+mod language_tests {
+    use crate::builder::{Builder, ParseStrategy};
+
+    //Compresses the string with both parse strategies, checking it decompresses correctly and returning the smallest size
+    fn round_trip(string: & str) -> usize {
+        [ParseStrategy::Greedy, ParseStrategy::Optimal].into_iter().map(|strategy| {
+            let engine = Builder::default().set_parse_strategy(strategy).engine();
+
+            let bytes = engine.compress(string);
+
+            assert_eq!(engine.decompress(bytes.as_slice()).unwrap(), string);
+
+            bytes.len()
+        }).min().unwrap()
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!(cfg!(byte_size_language = "en"), crate::LANGUAGE == "en");
+        assert_eq!(cfg!(byte_size_language = "de"), crate::LANGUAGE == "de");
+        assert_eq!(cfg!(byte_size_language = "es"), crate::LANGUAGE == "es");
+        assert_eq!(cfg!(byte_size_language = "fr"), crate::LANGUAGE == "fr");
+    }
+
+    #[test]
+    fn test_samples() {
+        let samples = [
+            "The government announced a new plan for the city",
+            "Die Regierung hat heute eine neue Entscheidung über die Zukunft der Stadt getroffen",
+            "El gobierno presentó hoy un nuevo proyecto para la ciudad",
+            "Le gouvernement a présenté aujourd'hui un nouveau projet pour la ville",
+        ];
+
+        for sample in samples {
+            round_trip(sample);
+        }
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "de")]
+    fn test_german() {
+        let string = "Die Regierung hat heute eine neue Entscheidung über die Zukunft der Stadt getroffen";
+
+        assert!(round_trip(string) * 2 < string.len());
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "es")]
+    fn test_spanish() {
+        let string = "El gobierno presentó hoy un nuevo proyecto para la ciudad";
+
+        assert!(round_trip(string) * 2 < string.len());
+    }
+
+    #[test]
+    #[cfg(byte_size_language = "fr")]
+    fn test_french() {
+        let string = "Le gouvernement a présenté aujourd'hui un nouveau projet pour la ville";
+
+        assert!(round_trip(string) * 2 < string.len());
+    }
+}
@@ -0,0 +1,167 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::builder::Builder;
+
+///Chooses the custom strings that give the best compression for a sample corpus
+///
+/// The trainer looks for substrings that are common in the corpus, then repeatedly adds whichever one saves the most bytes
+/// (by compressing the whole corpus with it added to the custom list) until the custom list is full or no substring saves any bytes.
+///
+/// The corpus should be a representative sample of the strings that will be compressed. Training is slow, as the corpus is compressed many times.
+pub struct Trainer {
+    custom_spaces: bool,
+    max_length: usize,
+    candidates: usize,
+}
+
+impl Default for Trainer {
+    fn default() -> Self {
+        Self {
+            custom_spaces: false,
+            max_length: 24,
+            candidates: 64,
+        }
+    }
+}
+
+impl Trainer {
+
+    ///Determines whether the trained custom list uses space prefixes. See [Builder::set_custom_spaces] for more information.
+    ///
+    /// If true, at most 16 custom strings are chosen instead of 32.
+    pub fn set_custom_spaces(& mut self, spaces: bool) -> & mut Self {
+        self.custom_spaces = spaces;
+        self
+    }
+
+    ///Sets the length (in bytes) of the longest substring that will be considered as a custom string
+    pub fn set_max_length(& mut self, length: usize) -> & mut Self {
+        self.max_length = length;
+        self
+    }
+
+    ///Sets how many of the most promising substrings are tried when choosing each custom string.
+    ///
+    /// Larger values may find a slightly better list, but training takes longer.
+    pub fn set_candidates(& mut self, candidates: usize) -> & mut Self {
+        self.candidates = candidates;
+        self
+    }
+
+    ///Chooses the custom strings for the corpus, in the order they should be added to the custom list
+    pub fn select<'a, I: IntoIterator<Item = & 'a str>>(&self, corpus: I) -> Vec<String> {
+
+        let corpus: Vec<_> = corpus.into_iter().collect();
+
+        let max_custom = if self.custom_spaces { 16 } else { 32 };
+
+        let mut selected: Vec<String> = Vec::new();
+
+        let mut best_size = self.corpus_size(&corpus, &selected);
+
+        while selected.len() < max_custom {
+
+            //Remove the custom strings chosen so far from the corpus, so the shortlist is made up of substrings that aren't already covered
+            let mut fragments = corpus.clone();
+
+            for custom in &selected {
+                fragments = fragments.iter().flat_map(|fragment| fragment.split(custom.as_str())).collect();
+            }
+
+            let mut shortlist = self.shortlist(&fragments);
+
+            let mut best = None;
+
+            for (i, candidate) in shortlist.iter().enumerate() {
+                selected.push(candidate.clone());
+
+                let size = self.corpus_size(&corpus, &selected);
+
+                if size < best_size {
+                    best_size = size;
+                    best = Some(i);
+                }
+
+                selected.pop();
+            }
+
+            //Stop as soon as no candidate makes the corpus any smaller
+            match best {
+                Some(i) => selected.push(shortlist.remove(i)),
+                None => break,
+            }
+        }
+
+        selected
+    }
+
+    ///Chooses the custom strings for the corpus (see [Trainer::select]) and returns a [Builder] that uses them
+    pub fn train<'a, I: IntoIterator<Item = & 'a str>>(&self, corpus: I) -> Builder {
+        let mut builder = Builder::empty();
+
+        builder.set_custom_spaces(self.custom_spaces).set_custom(self.select(corpus));
+
+        builder
+    }
+
+    //Total compressed size of the corpus using the custom list
+    fn corpus_size(&self, corpus: & [& str], custom: & [String]) -> usize {
+        let engine = Builder::empty().set_custom_spaces(self.custom_spaces).set_custom(custom.iter().cloned()).engine();
+
+        let mut buffer = Vec::new();
+
+        corpus.iter().map(|string| {
+            buffer.clear();
+            engine.compress_to_vec(string, & mut buffer)
+        }).sum()
+    }
+
+    //Finds the substrings that are most likely to save bytes as custom strings.
+    //
+    //Each substring is scored by how often it appears multiplied by how many bytes it saves over its normal encoding.
+    //This ignores the way custom strings interact with each other and the surrounding text, which is why the final choice is made by compressing the corpus
+    fn shortlist(&self, corpus: & [& str]) -> Vec<String> {
+
+        let mut counts: BTreeMap<& str, usize> = BTreeMap::new();
+
+        for string in corpus {
+            let boundaries: Vec<_> = string.char_indices().map(|(i, _)| i).chain([string.len()]).collect();
+
+            for (i, start) in boundaries.iter().enumerate() {
+                for end in &boundaries[i + 1..] {
+                    if end - start > self.max_length {
+                        break;
+                    }
+
+                    //Spaces are matched automatically with custom spaces, so there is no point including them
+                    let substring = &string[*start..*end];
+
+                    if self.custom_spaces && substring.starts_with(' ') {
+                        break;
+                    }
+
+                    *counts.entry(substring).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let engine = Builder::empty().engine();
+
+        let mut scored: Vec<_> = counts.into_iter().filter_map(|(substring, count)| {
+            //Custom strings are encoded as two bytes, so anything that already compresses to two bytes or less can't be improved
+            let saving = engine.compress(substring).len().checked_sub(2)?;
+
+            if saving == 0 || count < 2 {
+                return None;
+            }
+
+            Some((saving * count, substring))
+        }).collect();
+
+        //Sort by score, breaking ties by the substring itself so the result doesn't depend on hash map order
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        scored.into_iter().take(self.candidates).map(|(_, substring)| substring.to_string()).collect()
+    }
+}